@@ -0,0 +1,120 @@
+// Draws behavior tree files as graphs, for reviewing tree changes.
+//
+//   bt_export dot|mermaid <tree> [--expand] [--trace <trace> [--entity <entity>]]
+//
+// The tree's extension says whose nodes it uses: `.tower.bt.ron` or `.enemy.bt.ron`. With
+// --expand, subtree references are filled in from the library next to the tree. With --trace,
+// nodes are colored by the last recorded tick, of the given entity if there is one.
+use std::path::Path;
+
+use anyhow::{anyhow, bail};
+use serde::{de::DeserializeOwned, Serialize};
+
+use ludum_dare_51::{
+    bt::*,
+    ten_seconds::{enemies::tree_nodes::EnemyNode, towers::tree_nodes::TowerNode},
+};
+
+const USAGE: &str =
+    "usage: bt_export dot|mermaid <tree> [--expand] [--trace <trace> [--entity <entity>]]";
+
+struct Options<'a> {
+    mermaid: bool,
+    tree: &'a Path,
+    expand: bool,
+    trace: Option<&'a Path>,
+    entity: Option<u64>,
+}
+
+fn main() -> anyhow::Result<()> {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let args: Vec<&str> = args.iter().map(|arg| arg.as_str()).collect();
+    let options = match parse_args(&args) {
+        Ok(options) => options,
+        Err(err) => {
+            eprintln!("{}\n{}", err, USAGE);
+            std::process::exit(2);
+        }
+    };
+    let file_name = options
+        .tree
+        .file_name()
+        .and_then(|file_name| file_name.to_str())
+        .unwrap_or_default();
+    let graph = if file_name.ends_with(".tower.bt.ron") {
+        export::<TowerNode>(&options, "library.tower.lib.ron")?
+    } else if file_name.ends_with(".enemy.bt.ron") {
+        export::<EnemyNode>(&options, "library.enemy.lib.ron")?
+    } else {
+        bail!("{} is not a .tower.bt.ron or .enemy.bt.ron file", file_name);
+    };
+    print!("{}", graph);
+    Ok(())
+}
+
+fn parse_args<'a>(args: &'a [&'a str]) -> anyhow::Result<Options<'a>> {
+    let (mermaid, tree, mut rest) = match args {
+        ["dot", tree, rest @ ..] => (false, tree, rest),
+        ["mermaid", tree, rest @ ..] => (true, tree, rest),
+        _ => bail!("expected a format and a tree"),
+    };
+    let mut options = Options {
+        mermaid,
+        tree: Path::new(tree),
+        expand: false,
+        trace: None,
+        entity: None,
+    };
+    loop {
+        rest = match rest {
+            [] => return Ok(options),
+            ["--expand", rest @ ..] => {
+                options.expand = true;
+                rest
+            }
+            ["--trace", trace, rest @ ..] => {
+                options.trace = Some(Path::new(trace));
+                rest
+            }
+            ["--entity", entity, rest @ ..] => {
+                options.entity = Some(entity.parse()?);
+                rest
+            }
+            [arg, ..] => bail!("unexpected {}", arg),
+        };
+    }
+}
+
+fn export<U>(options: &Options, library_name: &str) -> anyhow::Result<String>
+where
+    U: UserNodeDefinition + Clone + Serialize,
+    U::Predicate: Serialize,
+    U::Scorer: Serialize,
+    BehaviorTreeDef<U>: DeserializeOwned,
+{
+    let mut tree_def: BehaviorTreeDef<U> = ron::from_str(&std::fs::read_to_string(options.tree)?)?;
+    if options.expand {
+        let library_path = options.tree.with_file_name(library_name);
+        let library: BehaviorTreeLibrary<U> =
+            ron::from_str(&std::fs::read_to_string(&library_path)?)?;
+        tree_def = tree_def
+            .expand(&library)
+            .map_err(|diagnostic| anyhow!("{}", diagnostic))?;
+    }
+    let audit = match options.trace {
+        Some(trace) => {
+            let entry = load_trace(trace)?
+                .into_iter()
+                .rev()
+                .find(|entry| options.entity.is_none_or(|entity| entry.entity == entity))
+                .ok_or_else(|| anyhow!("{} has no ticks to color by", trace.display()))?;
+            Some(entry.replay())
+        }
+        None => None,
+    };
+    Ok(if options.mermaid {
+        tree_def.to_mermaid(audit.as_ref())
+    } else {
+        tree_def.to_dot(audit.as_ref())
+    })
+}
@@ -0,0 +1,80 @@
+// Replays and compares behavior tree traces recorded with BT_TRACE.
+//
+//   bt_trace show <trace>
+//   bt_trace diff <before> <after>
+use std::path::Path;
+
+use ludum_dare_51::bt::*;
+
+const USAGE: &str = "usage: bt_trace show <trace> | bt_trace diff <before> <after>";
+
+fn main() -> anyhow::Result<()> {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let args: Vec<&str> = args.iter().map(|arg| arg.as_str()).collect();
+    match args.as_slice() {
+        ["show", path] => show(Path::new(path)),
+        ["diff", before, after] => diff(Path::new(before), Path::new(after)),
+        _ => {
+            eprintln!("{}", USAGE);
+            std::process::exit(2);
+        }
+    }
+}
+
+fn show(path: &Path) -> anyhow::Result<()> {
+    for entry in load_trace(path)? {
+        println!("frame {} entity {}", entry.frame, entry.entity);
+        let mut depth = 1;
+        for event in entry.events.iter() {
+            match event {
+                BehaviorTreeMarker::Enter(name) => {
+                    println!("{}> {}", "  ".repeat(depth), name);
+                    depth += 1;
+                }
+                BehaviorTreeMarker::Marker(name) => {
+                    println!("{}* {}", "  ".repeat(depth), name);
+                }
+                BehaviorTreeMarker::Exit(name, state) => {
+                    depth = depth.saturating_sub(1).max(1);
+                    println!("{}< {} {:?}", "  ".repeat(depth), name, state);
+                }
+            }
+        }
+        let unfinished = entry.replay();
+        if !unfinished.get_place().is_empty() {
+            println!("  never exited: {:?}", unfinished.get_place());
+        }
+    }
+    Ok(())
+}
+
+fn diff(before: &Path, after: &Path) -> anyhow::Result<()> {
+    let differences = diff_traces(&load_trace(before)?, &load_trace(after)?);
+    for difference in differences.iter() {
+        match difference {
+            TraceDifference::OnlyBefore { frame, entity } => {
+                println!("frame {} entity {}: only before", frame, entity);
+            }
+            TraceDifference::OnlyAfter { frame, entity } => {
+                println!("frame {} entity {}: only after", frame, entity);
+            }
+            TraceDifference::Changed {
+                frame,
+                entity,
+                index,
+                before,
+                after,
+            } => {
+                println!(
+                    "frame {} entity {}: event {} was {:?}, now {:?}",
+                    frame, entity, index, before, after
+                );
+            }
+        }
+    }
+    println!("{} difference(s)", differences.len());
+    if !differences.is_empty() {
+        std::process::exit(1);
+    }
+    Ok(())
+}
@@ -0,0 +1,199 @@
+use std::cmp::Ordering;
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+pub enum BlackboardValue {
+    Bool(bool),
+    Int(i32),
+    Float(f32),
+    Text(String),
+    Vector(f32, f32),
+    Id(u64),
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Copy, Clone)]
+pub enum BlackboardComparison {
+    Equal,
+    NotEqual,
+    Less,
+    LessOrEqual,
+    Greater,
+    GreaterOrEqual,
+}
+
+impl BlackboardValue {
+    fn partial_cmp(&self, other: &BlackboardValue) -> Option<Ordering> {
+        match (self, other) {
+            (BlackboardValue::Int(a), BlackboardValue::Int(b)) => a.partial_cmp(b),
+            (BlackboardValue::Int(a), BlackboardValue::Float(b)) => (*a as f32).partial_cmp(b),
+            (BlackboardValue::Float(a), BlackboardValue::Int(b)) => a.partial_cmp(&(*b as f32)),
+            (BlackboardValue::Float(a), BlackboardValue::Float(b)) => a.partial_cmp(b),
+            (a, b) if a == b => Some(Ordering::Equal),
+            _ => None,
+        }
+    }
+
+    pub fn compare(&self, comparison: BlackboardComparison, other: &BlackboardValue) -> bool {
+        let ordering = self.partial_cmp(other);
+        match comparison {
+            BlackboardComparison::Equal => ordering == Some(Ordering::Equal),
+            BlackboardComparison::NotEqual => ordering != Some(Ordering::Equal),
+            BlackboardComparison::Less => ordering == Some(Ordering::Less),
+            BlackboardComparison::LessOrEqual => {
+                ordering == Some(Ordering::Less) || ordering == Some(Ordering::Equal)
+            }
+            BlackboardComparison::Greater => ordering == Some(Ordering::Greater),
+            BlackboardComparison::GreaterOrEqual => {
+                ordering == Some(Ordering::Greater) || ordering == Some(Ordering::Equal)
+            }
+        }
+    }
+}
+
+// Anything that can be stored on a blackboard. Reading a key back as the wrong type gives None.
+pub trait BlackboardType: Sized {
+    fn into_value(self) -> BlackboardValue;
+
+    fn from_value(value: &BlackboardValue) -> Option<Self>;
+}
+
+impl BlackboardType for bool {
+    fn into_value(self) -> BlackboardValue {
+        BlackboardValue::Bool(self)
+    }
+
+    fn from_value(value: &BlackboardValue) -> Option<Self> {
+        match value {
+            BlackboardValue::Bool(value) => Some(*value),
+            _ => None,
+        }
+    }
+}
+
+impl BlackboardType for i32 {
+    fn into_value(self) -> BlackboardValue {
+        BlackboardValue::Int(self)
+    }
+
+    fn from_value(value: &BlackboardValue) -> Option<Self> {
+        match value {
+            BlackboardValue::Int(value) => Some(*value),
+            _ => None,
+        }
+    }
+}
+
+impl BlackboardType for f32 {
+    fn into_value(self) -> BlackboardValue {
+        BlackboardValue::Float(self)
+    }
+
+    fn from_value(value: &BlackboardValue) -> Option<Self> {
+        match value {
+            BlackboardValue::Float(value) => Some(*value),
+            _ => None,
+        }
+    }
+}
+
+impl BlackboardType for String {
+    fn into_value(self) -> BlackboardValue {
+        BlackboardValue::Text(self)
+    }
+
+    fn from_value(value: &BlackboardValue) -> Option<Self> {
+        match value {
+            BlackboardValue::Text(value) => Some(value.clone()),
+            _ => None,
+        }
+    }
+}
+
+// Values shared between the nodes of one tree instance.
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct Blackboard {
+    values: HashMap<String, BlackboardValue>,
+}
+
+impl Blackboard {
+    pub fn get<T: BlackboardType>(&self, key: &str) -> Option<T> {
+        self.values.get(key).and_then(T::from_value)
+    }
+
+    pub fn set<T: BlackboardType>(&mut self, key: &str, value: T) {
+        self.set_value(key, value.into_value());
+    }
+
+    pub fn get_value(&self, key: &str) -> Option<&BlackboardValue> {
+        self.values.get(key)
+    }
+
+    pub fn set_value(&mut self, key: &str, value: BlackboardValue) {
+        self.values.insert(key.to_string(), value);
+    }
+
+    pub fn clear(&mut self, key: &str) {
+        self.values.remove(key);
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&String, &BlackboardValue)> {
+        self.values.iter()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn values_come_back_as_they_were_set() {
+        let mut blackboard = Blackboard::default();
+        blackboard.set("flag", true);
+        blackboard.set("count", 3);
+        blackboard.set("speed", 1.5f32);
+        blackboard.set("name", "thief".to_string());
+        assert_eq!(blackboard.get("flag"), Some(true));
+        assert_eq!(blackboard.get("count"), Some(3));
+        assert_eq!(blackboard.get("speed"), Some(1.5f32));
+        assert_eq!(blackboard.get("name"), Some("thief".to_string()));
+        assert_eq!(
+            blackboard.get_value("count"),
+            Some(&BlackboardValue::Int(3))
+        );
+    }
+
+    #[test]
+    fn wrong_types_and_missing_keys_read_as_none() {
+        let mut blackboard = Blackboard::default();
+        blackboard.set("count", 3);
+        assert_eq!(blackboard.get::<f32>("count"), None);
+        assert_eq!(blackboard.get::<bool>("count"), None);
+        assert_eq!(blackboard.get::<i32>("missing"), None);
+        blackboard.clear("count");
+        assert_eq!(blackboard.get::<i32>("count"), None);
+    }
+
+    #[test]
+    fn ints_and_floats_compare_with_each_other() {
+        use BlackboardComparison::*;
+        let int = BlackboardValue::Int(2);
+        let float = BlackboardValue::Float(2.5);
+        assert!(int.compare(Less, &float));
+        assert!(float.compare(Greater, &int));
+        assert!(BlackboardValue::Float(2.).compare(Equal, &int));
+        assert!(int.compare(GreaterOrEqual, &BlackboardValue::Float(2.)));
+        assert!(!int.compare(LessOrEqual, &BlackboardValue::Float(1.5)));
+    }
+
+    #[test]
+    fn other_types_only_match_themselves() {
+        use BlackboardComparison::*;
+        let text = BlackboardValue::Text("a".to_string());
+        assert!(text.compare(Equal, &BlackboardValue::Text("a".to_string())));
+        assert!(!text.compare(Less, &BlackboardValue::Text("b".to_string())));
+        assert!(text.compare(NotEqual, &BlackboardValue::Int(0)));
+        assert!(!BlackboardValue::Bool(true).compare(Greater, &BlackboardValue::Bool(false)));
+    }
+}
@@ -0,0 +1,316 @@
+use std::{collections::HashMap, fmt::Write};
+
+use serde::Serialize;
+
+use super::{
+    tree_def::child_path, BehaviorTreeAudit, BehaviorTreeDef, BehaviorTreeMarker,
+    BehaviorTreeState, UserNodeDefinition,
+};
+
+struct GraphNode {
+    kind: String,
+    name: String,
+    params: Option<String>,
+    state: Option<BehaviorTreeState>,
+}
+
+struct GraphEdge {
+    from: usize,
+    to: usize,
+    // Weights and scorers, which belong to the link rather than the node.
+    label: Option<String>,
+}
+
+#[derive(Default)]
+struct Graph {
+    nodes: Vec<GraphNode>,
+    edges: Vec<GraphEdge>,
+}
+
+fn state_color(state: BehaviorTreeState) -> &'static str {
+    match state {
+        BehaviorTreeState::Complete => "#9be79b",
+        BehaviorTreeState::Failed => "#f28b82",
+        BehaviorTreeState::Waiting => "#fde293",
+        BehaviorTreeState::WaitingForGas => "#fbbc72",
+    }
+}
+
+// The state each node last exited with.
+fn last_states(audit: &BehaviorTreeAudit) -> HashMap<&String, BehaviorTreeState> {
+    audit
+        .get_events()
+        .iter()
+        .filter_map(|event| match event {
+            BehaviorTreeMarker::Exit(name, state) => Some((name, *state)),
+            _ => None,
+        })
+        .collect()
+}
+
+fn to_ron<T: Serialize>(value: &T) -> String {
+    ron::to_string(value).unwrap_or_else(|err| format!("<{}>", err))
+}
+
+impl<U: UserNodeDefinition> BehaviorTreeDef<U>
+where
+    U: Serialize,
+    U::Predicate: Serialize,
+    U::Scorer: Serialize,
+{
+    // Graphviz source for the tree. With an audit, nodes are colored by how they last exited.
+    pub fn to_dot(&self, audit: Option<&BehaviorTreeAudit>) -> String {
+        let graph = self.to_graph(audit);
+        let escape = |text: &str| text.replace('\\', "\\\\").replace('"', "\\\"");
+        let mut dot = String::new();
+        writeln!(dot, "digraph behavior_tree {{").unwrap();
+        writeln!(
+            dot,
+            "    node [shape=box, style=\"rounded,filled\", fillcolor=white, fontname=Helvetica];"
+        )
+        .unwrap();
+        for (index, node) in graph.nodes.iter().enumerate() {
+            let mut label = format!("{}\\n{}", escape(&node.kind), escape(&node.name));
+            if let Some(params) = &node.params {
+                write!(label, "\\n{}", escape(params)).unwrap();
+            }
+            match node.state {
+                Some(state) => writeln!(
+                    dot,
+                    "    n{} [label=\"{}\", fillcolor=\"{}\"];",
+                    index,
+                    label,
+                    state_color(state)
+                ),
+                None => writeln!(dot, "    n{} [label=\"{}\"];", index, label),
+            }
+            .unwrap();
+        }
+        for edge in graph.edges.iter() {
+            match &edge.label {
+                Some(label) => writeln!(
+                    dot,
+                    "    n{} -> n{} [label=\"{}\"];",
+                    edge.from,
+                    edge.to,
+                    escape(label)
+                ),
+                None => writeln!(dot, "    n{} -> n{};", edge.from, edge.to),
+            }
+            .unwrap();
+        }
+        writeln!(dot, "}}").unwrap();
+        dot
+    }
+
+    // Mermaid flowchart source for the tree, which GitHub renders in markdown. With an audit,
+    // nodes are colored by how they last exited.
+    pub fn to_mermaid(&self, audit: Option<&BehaviorTreeAudit>) -> String {
+        let graph = self.to_graph(audit);
+        let escape = |text: &str| text.replace('"', "#quot;");
+        let mut mermaid = String::new();
+        writeln!(mermaid, "flowchart TD").unwrap();
+        for (index, node) in graph.nodes.iter().enumerate() {
+            let mut label = format!("{}<br/>{}", escape(&node.kind), escape(&node.name));
+            if let Some(params) = &node.params {
+                write!(label, "<br/>{}", escape(params)).unwrap();
+            }
+            writeln!(mermaid, "    n{}[\"{}\"]", index, label).unwrap();
+        }
+        for edge in graph.edges.iter() {
+            match &edge.label {
+                Some(label) => writeln!(
+                    mermaid,
+                    "    n{} -->|\"{}\"| n{}",
+                    edge.from,
+                    escape(label),
+                    edge.to
+                ),
+                None => writeln!(mermaid, "    n{} --> n{}", edge.from, edge.to),
+            }
+            .unwrap();
+        }
+        for (index, node) in graph.nodes.iter().enumerate() {
+            if let Some(state) = node.state {
+                writeln!(mermaid, "    style n{} fill:{}", index, state_color(state)).unwrap();
+            }
+        }
+        mermaid
+    }
+
+    fn to_graph(&self, audit: Option<&BehaviorTreeAudit>) -> Graph {
+        let states = audit.map(last_states).unwrap_or_default();
+        let mut graph = Graph::default();
+        self.add_to_graph("root".to_string(), None, &states, &mut graph);
+        graph
+    }
+
+    fn add_to_graph(
+        &self,
+        id: String,
+        parent: Option<(usize, Option<String>)>,
+        states: &HashMap<&String, BehaviorTreeState>,
+        graph: &mut Graph,
+    ) {
+        let (kind, params) = self.describe();
+        let name = match self {
+            BehaviorTreeDef::User(node_def) => node_def.create_node().get_name().clone(),
+            _ => id.clone(),
+        };
+        let index = graph.nodes.len();
+        graph.nodes.push(GraphNode {
+            kind,
+            state: states.get(&name).copied(),
+            name,
+            params,
+        });
+        if let Some((from, label)) = parent {
+            graph.edges.push(GraphEdge {
+                from,
+                to: index,
+                label,
+            });
+        }
+        let labels: Vec<Option<String>> = match self {
+            BehaviorTreeDef::UtilitySelector(scored_node_defs, _) => scored_node_defs
+                .iter()
+                .map(|(scorer, _)| Some(to_ron(scorer)))
+                .collect(),
+            BehaviorTreeDef::WeightedRandom(weighted_node_defs) => weighted_node_defs
+                .iter()
+                .map(|(weight, _)| Some(weight.to_string()))
+                .collect(),
+            _ => Vec::new(),
+        };
+        for (child_index, node_def) in self.get_children().into_iter().enumerate() {
+            let label = labels.get(child_index).cloned().flatten();
+            node_def.add_to_graph(
+                child_path(&id, child_index),
+                Some((index, label)),
+                states,
+                graph,
+            );
+        }
+    }
+
+    // The node's kind, and whatever it was given besides its children.
+    fn describe(&self) -> (String, Option<String>) {
+        let (kind, params) = match self {
+            BehaviorTreeDef::Sequence(_) => ("Sequence", None),
+            BehaviorTreeDef::Selector(_) => ("Selector", None),
+            BehaviorTreeDef::ReactiveSequence(_) => ("ReactiveSequence", None),
+            BehaviorTreeDef::ReactiveSelector(_) => ("ReactiveSelector", None),
+            BehaviorTreeDef::Parallel(_, success_policy, failure_policy) => (
+                "Parallel",
+                Some(format!(
+                    "success: {:?}, failure: {:?}",
+                    success_policy, failure_policy
+                )),
+            ),
+            BehaviorTreeDef::UtilitySelector(_, hysteresis) => (
+                "UtilitySelector",
+                Some(format!("hysteresis: {}", hysteresis)),
+            ),
+            BehaviorTreeDef::RandomSelector(_) => ("RandomSelector", None),
+            BehaviorTreeDef::WeightedRandom(_) => ("WeightedRandom", None),
+            BehaviorTreeDef::Repeat(_, repeats) => ("Repeat", Some(format!("runs: {}", repeats))),
+            BehaviorTreeDef::RepeatUntilSuccess(_) => ("RepeatUntilSuccess", None),
+            BehaviorTreeDef::RepeatUntilFail(_) => ("RepeatUntilFail", None),
+            BehaviorTreeDef::Succeeder(_) => ("Succeeder", None),
+            BehaviorTreeDef::Failer(_) => ("Failer", None),
+            BehaviorTreeDef::Inverter(_) => ("Inverter", None),
+            BehaviorTreeDef::Cooldown(seconds, _) => {
+                ("Cooldown", Some(format!("seconds: {}", seconds)))
+            }
+            BehaviorTreeDef::Timeout(seconds, _) => {
+                ("Timeout", Some(format!("seconds: {}", seconds)))
+            }
+            BehaviorTreeDef::Delay(seconds, _) => ("Delay", Some(format!("seconds: {}", seconds))),
+            BehaviorTreeDef::RateLimit(runs, seconds, _) => (
+                "RateLimit",
+                Some(format!("runs: {}, seconds: {}", runs, seconds)),
+            ),
+            BehaviorTreeDef::SetBlackboard(key, value) => (
+                "SetBlackboard",
+                Some(format!("{} = {}", key, to_ron(value))),
+            ),
+            BehaviorTreeDef::CompareBlackboard(key, comparison, value) => (
+                "CompareBlackboard",
+                Some(format!("{} {:?} {}", key, comparison, to_ron(value))),
+            ),
+            BehaviorTreeDef::ClearBlackboard(key) => ("ClearBlackboard", Some(key.clone())),
+            BehaviorTreeDef::Condition(predicate) => ("Condition", Some(to_ron(predicate))),
+            BehaviorTreeDef::Guard(predicate, _) => ("Guard", Some(to_ron(predicate))),
+            BehaviorTreeDef::Ref(name, args) => {
+                let mut args: Vec<String> = args
+                    .iter()
+                    .map(|(param, value)| format!("{}: {}", param, value))
+                    .collect();
+                args.sort();
+                ("Ref", Some(format!("{}({})", name, args.join(", "))))
+            }
+            BehaviorTreeDef::User(node_def) => {
+                // Written as `Kind(params)`, so split the kind off the front.
+                let node = to_ron(node_def);
+                return match node.find('(') {
+                    Some(start) if node.ends_with(')') => (
+                        node[..start].to_string(),
+                        Some(node[start + 1..node.len() - 1].to_string()),
+                    ),
+                    _ => (node, None),
+                };
+            }
+        };
+        (kind.to_string(), params)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::bt::{testing::*, BehaviorTreeDef, BehaviorTreeState::*};
+
+    fn tree_def() -> BehaviorTreeDef<MockNode> {
+        ron::from_str(
+            r#"Selector([
+                Guard(Fact("ready"), User(Leaf("a"))),
+                WeightedRandom([(2.5, User(Leaf("b")))]),
+            ])"#,
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn dot_has_kinds_names_and_params() {
+        let dot = tree_def().to_dot(None);
+        assert!(dot.starts_with("digraph behavior_tree {\n"));
+        assert!(dot.contains("n0 [label=\"Selector\\nroot\"];"));
+        assert!(dot.contains("n1 [label=\"Guard\\nroot/0\\nFact(\\\"ready\\\")\"];"));
+        assert!(dot.contains("n2 [label=\"Leaf\\na\\n\\\"a\\\"\"];"));
+        assert!(dot.contains("n0 -> n1;"));
+        assert!(dot.contains("n3 -> n4 [label=\"2.5\"];"));
+    }
+
+    #[test]
+    fn mermaid_has_kinds_names_and_params() {
+        let mermaid = tree_def().to_mermaid(None);
+        assert!(mermaid.starts_with("flowchart TD\n"));
+        assert!(mermaid.contains("n1[\"Guard<br/>root/0<br/>Fact(#quot;ready#quot;)\"]"));
+        assert!(mermaid.contains("n3 -->|\"2.5\"| n4"));
+        assert!(!mermaid.contains("style"));
+    }
+
+    #[test]
+    fn audited_nodes_are_colored_by_their_last_state() {
+        let mut harness = TreeHarness::new(&ron::to_string(&tree_def()).unwrap());
+        let tick = harness.tick(&MockModel::new().leaf("b", Complete));
+        let dot = tree_def().to_dot(Some(&tick.audit));
+        assert!(dot.contains("n0 [label=\"Selector\\nroot\", fillcolor=\"#9be79b\"];"));
+        assert!(dot.contains(
+            "n1 [label=\"Guard\\nroot/0\\nFact(\\\"ready\\\")\", fillcolor=\"#f28b82\"];"
+        ));
+        // The guard failed without running its node.
+        assert!(dot.contains("n2 [label=\"Leaf\\na\\n\\\"a\\\"\"];"));
+        let mermaid = tree_def().to_mermaid(Some(&tick.audit));
+        assert!(mermaid.contains("style n4 fill:#9be79b"));
+    }
+}
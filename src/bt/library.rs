@@ -0,0 +1,290 @@
+use std::{collections::HashMap, marker::PhantomData};
+
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+
+use super::{
+    tree_def::child_path, BehaviorTreeDef, BehaviorTreeDiagnostic, BehaviorTreeProblem,
+    UserNodeDefinition,
+};
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct BehaviorTreeSubtree {
+    pub params: Vec<String>,
+    // RON for a tree definition, with `$param` wherever a parameter's value goes. Inside a
+    // string, as in "$param", the value is escaped into it. Anywhere else it is pasted in as RON,
+    // and must be a single value.
+    pub tree: String,
+}
+
+// Named subtrees that definitions can share through Ref.
+#[derive(Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct BehaviorTreeLibrary<U> {
+    subtrees: HashMap<String, BehaviorTreeSubtree>,
+    #[serde(skip)]
+    user_nodes: PhantomData<fn() -> U>,
+}
+
+impl<U> Default for BehaviorTreeLibrary<U> {
+    fn default() -> Self {
+        BehaviorTreeLibrary {
+            subtrees: HashMap::new(),
+            user_nodes: PhantomData,
+        }
+    }
+}
+
+impl<U> BehaviorTreeLibrary<U> {
+    pub fn get(&self, name: &str) -> Option<&BehaviorTreeSubtree> {
+        self.subtrees.get(name)
+    }
+}
+
+impl<U: UserNodeDefinition> BehaviorTreeLibrary<U>
+where
+    BehaviorTreeDef<U>: DeserializeOwned,
+{
+    // Fills in a subtree's parameters and parses it. Refs inside it are left for the caller.
+    fn instantiate(
+        &self,
+        name: &str,
+        args: &HashMap<String, String>,
+    ) -> Result<BehaviorTreeDef<U>, BehaviorTreeProblem> {
+        let subtree = self
+            .get(name)
+            .ok_or_else(|| BehaviorTreeProblem::UnknownSubtree(name.to_string()))?;
+        if let Some(param) = subtree
+            .params
+            .iter()
+            .find(|param| !args.contains_key(*param))
+        {
+            return Err(BehaviorTreeProblem::MissingParam(
+                name.to_string(),
+                param.clone(),
+            ));
+        }
+        if let Some(arg) = args.keys().find(|arg| !subtree.params.contains(arg)) {
+            return Err(BehaviorTreeProblem::UnknownParam(
+                name.to_string(),
+                arg.clone(),
+            ));
+        }
+        let tree = substitute(name, &subtree.tree, args)?;
+        ron::from_str(&tree)
+            .map_err(|err| BehaviorTreeProblem::InvalidSubtree(name.to_string(), err.to_string()))
+    }
+}
+
+// Replaces every `$param` in a subtree with its value.
+fn substitute(
+    name: &str,
+    tree: &str,
+    args: &HashMap<String, String>,
+) -> Result<String, BehaviorTreeProblem> {
+    let mut result = String::with_capacity(tree.len());
+    let mut in_string = false;
+    let mut chars = tree.char_indices();
+    while let Some((start, c)) = chars.next() {
+        match c {
+            '\\' if in_string => {
+                result.push(c);
+                if let Some((_, escaped)) = chars.next() {
+                    result.push(escaped);
+                }
+            }
+            '"' => {
+                in_string = !in_string;
+                result.push(c);
+            }
+            '$' => {
+                // The whole name, so `$speed` doesn't match the start of `$speed_up`.
+                let after = &tree[start + 1..];
+                let end = after
+                    .find(|c: char| !(c.is_alphanumeric() || c == '_'))
+                    .unwrap_or(after.len());
+                let param = &after[..end];
+                let value = args.get(param).ok_or_else(|| {
+                    BehaviorTreeProblem::UnknownParam(name.to_string(), param.to_string())
+                })?;
+                if in_string {
+                    for c in value.chars() {
+                        if c == '"' || c == '\\' {
+                            result.push('\\');
+                        }
+                        result.push(c);
+                    }
+                } else {
+                    ron::from_str::<ron::Value>(value).map_err(|err| {
+                        BehaviorTreeProblem::InvalidSubtree(
+                            name.to_string(),
+                            format!("{} is not a value for {}: {}", value, param, err),
+                        )
+                    })?;
+                    result.push_str(value);
+                }
+                for _ in 0..param.chars().count() {
+                    chars.next();
+                }
+            }
+            _ => result.push(c),
+        }
+    }
+    Ok(result)
+}
+
+impl<U: UserNodeDefinition + Clone> BehaviorTreeDef<U>
+where
+    BehaviorTreeDef<U>: DeserializeOwned,
+{
+    // Replaces every Ref with the subtree it names, so the result can be built.
+    pub fn expand(
+        &self,
+        library: &BehaviorTreeLibrary<U>,
+    ) -> Result<BehaviorTreeDef<U>, BehaviorTreeDiagnostic> {
+        self.expand_at("root".to_string(), library, &mut Vec::new())
+    }
+
+    // `refs` are the subtrees being expanded around this node, to catch one that contains itself.
+    fn expand_at(
+        &self,
+        id: String,
+        library: &BehaviorTreeLibrary<U>,
+        refs: &mut Vec<String>,
+    ) -> Result<BehaviorTreeDef<U>, BehaviorTreeDiagnostic> {
+        match self {
+            BehaviorTreeDef::Ref(name, args) => {
+                if refs.contains(name) {
+                    let mut cycle = refs.clone();
+                    cycle.push(name.clone());
+                    return Err(BehaviorTreeDiagnostic {
+                        node: id,
+                        problem: BehaviorTreeProblem::SubtreeCycle(cycle),
+                    });
+                }
+                let subtree =
+                    library
+                        .instantiate(name, args)
+                        .map_err(|problem| BehaviorTreeDiagnostic {
+                            node: id.clone(),
+                            problem,
+                        })?;
+                refs.push(name.clone());
+                let expanded = subtree.expand_at(id, library, refs);
+                refs.pop();
+                expanded
+            }
+            _ => {
+                let mut index = 0;
+                self.try_map_children(|node_def| {
+                    let expanded = node_def.expand_at(child_path(&id, index), library, refs);
+                    index += 1;
+                    expanded
+                })
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bt::{testing::*, BehaviorTreeSettings, BehaviorTreeState::*};
+
+    const LIBRARY: &str = r##"{
+        "twice": (
+            params: ["speed", "speed_up", "times"],
+            tree: r#"Repeat(Sequence([User(Leaf("$speed")), User(Leaf("$speed_up"))]), $times)"#,
+        ),
+        "named": (
+            params: ["name"],
+            tree: r#"Ref("twice", {"speed": "$name", "speed_up": "up", "times": "1"})"#,
+        ),
+        "ping": (params: [], tree: r#"Sequence([User(Leaf("a")), Ref("pong", {})])"#),
+        "pong": (params: [], tree: r#"Ref("ping", {})"#),
+    }"##;
+
+    // What goes wrong expanding a tree, if anything.
+    fn problem(tree: &str) -> Option<BehaviorTreeProblem> {
+        let tree_def: BehaviorTreeDef<MockNode> = ron::from_str(tree).unwrap();
+        let library: BehaviorTreeLibrary<MockNode> = ron::from_str(LIBRARY).unwrap();
+        tree_def
+            .expand(&library)
+            .err()
+            .map(|diagnostic| diagnostic.problem)
+    }
+
+    fn ran(tree: &str) -> Vec<String> {
+        let mut harness =
+            TreeHarness::build(tree, LIBRARY, &BehaviorTreeSettings::default()).unwrap();
+        let model = MockModel::new()
+            .leaf("slow", Complete)
+            .leaf("fast", Complete)
+            .leaf("up", Complete)
+            .leaf(r#"a"), User(Leaf("b"#, Complete);
+        let tick = harness.tick(&model);
+        assert_eq!(tick.state, Complete);
+        tick.ran
+    }
+
+    #[test]
+    fn refs_expand_with_their_params() {
+        assert_eq!(
+            ran(r#"Ref("twice", {"speed": "slow", "speed_up": "fast", "times": "2"})"#),
+            ["slow", "fast", "slow", "fast"]
+        );
+    }
+
+    #[test]
+    fn params_nest_through_refs() {
+        assert_eq!(ran(r#"Ref("named", {"name": "slow"})"#), ["slow", "up"]);
+    }
+
+    #[test]
+    fn strings_stay_strings() {
+        let leaf = r#"a"), User(Leaf("b"#;
+        let tree = format!(r#"Ref("named", {{"name": {:?}}})"#, leaf);
+        assert_eq!(ran(&tree), [leaf, "up"]);
+    }
+
+    #[test]
+    fn values_must_be_values() {
+        assert!(matches!(
+            problem(r#"Ref("twice", {"speed": "a", "speed_up": "b", "times": "1), Evil("})"#),
+            Some(BehaviorTreeProblem::InvalidSubtree(name, _)) if name == "twice"
+        ));
+    }
+
+    #[test]
+    fn params_must_match() {
+        assert_eq!(
+            problem(r#"Ref("twice", {"speed": "a", "times": "1"})"#),
+            Some(BehaviorTreeProblem::MissingParam(
+                "twice".to_string(),
+                "speed_up".to_string()
+            ))
+        );
+        assert_eq!(
+            problem(r#"Ref("named", {"name": "a", "speed": "b"})"#),
+            Some(BehaviorTreeProblem::UnknownParam(
+                "named".to_string(),
+                "speed".to_string()
+            ))
+        );
+        assert_eq!(
+            problem(r#"Ref("nothing", {})"#),
+            Some(BehaviorTreeProblem::UnknownSubtree("nothing".to_string()))
+        );
+    }
+
+    #[test]
+    fn cycles_are_caught() {
+        assert_eq!(
+            problem(r#"Sequence([Ref("ping", {})])"#),
+            Some(BehaviorTreeProblem::SubtreeCycle(vec![
+                "ping".to_string(),
+                "pong".to_string(),
+                "ping".to_string()
+            ]))
+        );
+    }
+}
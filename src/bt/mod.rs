@@ -1,4 +1,19 @@
+mod blackboard;
+mod export;
+mod library;
 mod nodes;
+mod registry;
+mod snapshot;
+#[cfg(test)]
+mod testing;
+mod trace;
 mod tree_def;
+mod validate;
+pub use blackboard::*;
+pub use library::*;
 pub use nodes::*;
+pub use registry::*;
+pub use snapshot::*;
+pub use trace::*;
 pub use tree_def::*;
+pub use validate::*;
@@ -0,0 +1,217 @@
+use std::marker::PhantomData;
+
+use crate::bt::*;
+
+pub struct SetBlackboard<M, C> {
+    name: String,
+    key: String,
+    value: BlackboardValue,
+    phantom: PhantomData<fn(&M, &mut C)>,
+}
+
+impl<M, C> SetBlackboard<M, C> {
+    pub fn new(name: String, key: String, value: BlackboardValue) -> Self {
+        SetBlackboard {
+            name,
+            key,
+            value,
+            phantom: PhantomData,
+        }
+    }
+}
+
+impl<M: 'static, C: 'static> BehaviorTree for SetBlackboard<M, C> {
+    type Model = M;
+    type Controller = C;
+    fn resume_with(
+        self: &mut Self,
+        _model: &Self::Model,
+        _controller: &mut Self::Controller,
+        blackboard: &mut Blackboard,
+        gas: &mut Option<i32>,
+        mut audit: &mut Option<&mut BehaviorTreeAudit>,
+    ) -> BehaviorTreeState {
+        audit.enter(self.get_name());
+        if !use_gas(gas) {
+            audit.exit(self.get_name(), BehaviorTreeState::WaitingForGas);
+            return BehaviorTreeState::WaitingForGas;
+        }
+        blackboard.set_value(&self.key, self.value.clone());
+        audit.exit(self.get_name(), BehaviorTreeState::Complete);
+        BehaviorTreeState::Complete
+    }
+
+    fn reset(self: &mut Self, _model: &Self::Model) {
+        // Nothing to do.
+    }
+
+    fn get_name(self: &Self) -> &String {
+        &self.name
+    }
+}
+
+pub struct CompareBlackboard<M, C> {
+    name: String,
+    key: String,
+    comparison: BlackboardComparison,
+    value: BlackboardValue,
+    phantom: PhantomData<fn(&M, &mut C)>,
+}
+
+impl<M, C> CompareBlackboard<M, C> {
+    pub fn new(
+        name: String,
+        key: String,
+        comparison: BlackboardComparison,
+        value: BlackboardValue,
+    ) -> Self {
+        CompareBlackboard {
+            name,
+            key,
+            comparison,
+            value,
+            phantom: PhantomData,
+        }
+    }
+}
+
+impl<M: 'static, C: 'static> BehaviorTree for CompareBlackboard<M, C> {
+    type Model = M;
+    type Controller = C;
+    fn resume_with(
+        self: &mut Self,
+        _model: &Self::Model,
+        _controller: &mut Self::Controller,
+        blackboard: &mut Blackboard,
+        gas: &mut Option<i32>,
+        mut audit: &mut Option<&mut BehaviorTreeAudit>,
+    ) -> BehaviorTreeState {
+        audit.enter(self.get_name());
+        if !use_gas(gas) {
+            audit.exit(self.get_name(), BehaviorTreeState::WaitingForGas);
+            return BehaviorTreeState::WaitingForGas;
+        }
+        // A missing key never matches, whatever the comparison.
+        let result = match blackboard.get_value(&self.key) {
+            Some(value) if value.compare(self.comparison, &self.value) => {
+                BehaviorTreeState::Complete
+            }
+            _ => BehaviorTreeState::Failed,
+        };
+        audit.exit(self.get_name(), result);
+        result
+    }
+
+    fn reset(self: &mut Self, _model: &Self::Model) {
+        // Nothing to do.
+    }
+
+    fn get_name(self: &Self) -> &String {
+        &self.name
+    }
+}
+
+pub struct ClearBlackboard<M, C> {
+    name: String,
+    key: String,
+    phantom: PhantomData<fn(&M, &mut C)>,
+}
+
+impl<M, C> ClearBlackboard<M, C> {
+    pub fn new(name: String, key: String) -> Self {
+        ClearBlackboard {
+            name,
+            key,
+            phantom: PhantomData,
+        }
+    }
+}
+
+impl<M: 'static, C: 'static> BehaviorTree for ClearBlackboard<M, C> {
+    type Model = M;
+    type Controller = C;
+    fn resume_with(
+        self: &mut Self,
+        _model: &Self::Model,
+        _controller: &mut Self::Controller,
+        blackboard: &mut Blackboard,
+        gas: &mut Option<i32>,
+        mut audit: &mut Option<&mut BehaviorTreeAudit>,
+    ) -> BehaviorTreeState {
+        audit.enter(self.get_name());
+        if !use_gas(gas) {
+            audit.exit(self.get_name(), BehaviorTreeState::WaitingForGas);
+            return BehaviorTreeState::WaitingForGas;
+        }
+        blackboard.clear(&self.key);
+        audit.exit(self.get_name(), BehaviorTreeState::Complete);
+        BehaviorTreeState::Complete
+    }
+
+    fn reset(self: &mut Self, _model: &Self::Model) {
+        // Nothing to do.
+    }
+
+    fn get_name(self: &Self) -> &String {
+        &self.name
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::bt::{testing::*, BehaviorTreeState::*};
+
+    #[test]
+    fn set_stores_its_value() {
+        let mut harness = TreeHarness::new(r#"SetBlackboard("count", Int(3))"#);
+        assert_eq!(harness.tick(&MockModel::new()).state, Complete);
+        assert_eq!(harness.blackboard.get("count"), Some(3));
+    }
+
+    #[test]
+    fn compare_checks_the_stored_value() {
+        let mut harness = TreeHarness::new(
+            r#"Sequence([
+                SetBlackboard("count", Int(3)),
+                CompareBlackboard("count", Less, Float(3.5)),
+            ])"#,
+        );
+        assert_eq!(harness.tick(&MockModel::new()).state, Complete);
+        let mut harness = TreeHarness::new(
+            r#"Sequence([
+                SetBlackboard("count", Int(3)),
+                CompareBlackboard("count", Greater, Float(3.5)),
+            ])"#,
+        );
+        assert_eq!(harness.tick(&MockModel::new()).state, Failed);
+    }
+
+    #[test]
+    fn compare_fails_on_a_missing_key() {
+        for comparison in ["Equal", "NotEqual", "Less", "GreaterOrEqual"] {
+            let mut harness = TreeHarness::new(&format!(
+                r#"CompareBlackboard("count", {}, Int(3))"#,
+                comparison
+            ));
+            assert_eq!(
+                harness.tick(&MockModel::new()).state,
+                Failed,
+                "{}",
+                comparison
+            );
+        }
+    }
+
+    #[test]
+    fn clear_removes_the_key() {
+        let mut harness = TreeHarness::new(
+            r#"Sequence([
+                SetBlackboard("count", Int(3)),
+                ClearBlackboard("count"),
+                CompareBlackboard("count", NotEqual, Int(3)),
+            ])"#,
+        );
+        assert_eq!(harness.tick(&MockModel::new()).state, Failed);
+        assert!(harness.blackboard.get_value("count").is_none());
+    }
+}
@@ -0,0 +1,184 @@
+use std::marker::PhantomData;
+
+use serde::{Deserialize, Serialize};
+
+use crate::bt::*;
+
+// A named check over a model, so trees can branch without a custom leaf for every question.
+pub trait BehaviorTreePredicate {
+    type Model;
+
+    fn check(&self, model: &Self::Model) -> bool;
+}
+
+// For trees whose model has no predicates.
+#[derive(Serialize, Deserialize)]
+pub enum NoPredicate<M> {
+    #[serde(skip)]
+    Never(PhantomData<fn(&M)>),
+}
+
+impl<M> Clone for NoPredicate<M> {
+    fn clone(&self) -> Self {
+        NoPredicate::Never(PhantomData)
+    }
+}
+
+impl<M> BehaviorTreePredicate for NoPredicate<M> {
+    type Model = M;
+
+    fn check(&self, _model: &Self::Model) -> bool {
+        false
+    }
+}
+
+pub struct Condition<P, C> {
+    name: String,
+    predicate: P,
+    phantom: PhantomData<fn(&mut C)>,
+}
+
+impl<P, C> Condition<P, C> {
+    pub fn new(name: String, predicate: P) -> Self {
+        Condition {
+            name,
+            predicate,
+            phantom: PhantomData,
+        }
+    }
+}
+
+impl<M: 'static, C: 'static, P: BehaviorTreePredicate<Model = M>> BehaviorTree for Condition<P, C> {
+    type Model = M;
+    type Controller = C;
+    fn resume_with(
+        self: &mut Self,
+        model: &Self::Model,
+        _controller: &mut Self::Controller,
+        _blackboard: &mut Blackboard,
+        gas: &mut Option<i32>,
+        mut audit: &mut Option<&mut BehaviorTreeAudit>,
+    ) -> BehaviorTreeState {
+        audit.enter(self.get_name());
+        if !use_gas(gas) {
+            audit.exit(self.get_name(), BehaviorTreeState::WaitingForGas);
+            return BehaviorTreeState::WaitingForGas;
+        }
+        let result = if self.predicate.check(model) {
+            BehaviorTreeState::Complete
+        } else {
+            BehaviorTreeState::Failed
+        };
+        audit.exit(self.get_name(), result);
+        result
+    }
+
+    fn reset(self: &mut Self, _model: &Self::Model) {
+        // Nothing to do.
+    }
+
+    fn get_name(self: &Self) -> &String {
+        &self.name
+    }
+}
+
+// Runs its node only while the predicate holds. Checked every tick, so a running node is
+// reset and the guard fails as soon as the predicate stops holding.
+pub struct Guard<P, M, C> {
+    name: String,
+    predicate: P,
+    node: Box<dyn BehaviorTree<Model = M, Controller = C> + Send + Sync>,
+}
+
+impl<P, M, C> Guard<P, M, C> {
+    pub fn new(
+        name: String,
+        predicate: P,
+        node: Box<dyn BehaviorTree<Model = M, Controller = C> + Send + Sync>,
+    ) -> Self {
+        Guard {
+            name,
+            predicate,
+            node,
+        }
+    }
+}
+
+impl<M: 'static, C: 'static, P: BehaviorTreePredicate<Model = M>> BehaviorTree for Guard<P, M, C> {
+    type Model = M;
+    type Controller = C;
+    fn resume_with(
+        self: &mut Self,
+        model: &Self::Model,
+        controller: &mut Self::Controller,
+        blackboard: &mut Blackboard,
+        gas: &mut Option<i32>,
+        mut audit: &mut Option<&mut BehaviorTreeAudit>,
+    ) -> BehaviorTreeState {
+        audit.enter(self.get_name());
+        if !use_gas(gas) {
+            audit.exit(self.get_name(), BehaviorTreeState::WaitingForGas);
+            return BehaviorTreeState::WaitingForGas;
+        }
+        if !self.predicate.check(model) {
+            self.node.reset(model);
+            audit.exit(self.get_name(), BehaviorTreeState::Failed);
+            return BehaviorTreeState::Failed;
+        }
+        let result = self
+            .node
+            .resume_with(model, controller, blackboard, gas, audit);
+        audit.exit(self.get_name(), result);
+        result
+    }
+
+    fn reset(self: &mut Self, model: &Self::Model) {
+        self.node.reset(model);
+    }
+
+    fn get_name(self: &Self) -> &String {
+        &self.name
+    }
+
+    fn get_children(
+        self: &Self,
+    ) -> Vec<&(dyn BehaviorTree<Model = Self::Model, Controller = Self::Controller> + Send + Sync)>
+    {
+        vec![&*self.node]
+    }
+
+    fn get_children_mut(
+        self: &mut Self,
+    ) -> Vec<
+        &mut (dyn BehaviorTree<Model = Self::Model, Controller = Self::Controller> + Send + Sync),
+    > {
+        vec![&mut *self.node]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::bt::{testing::*, BehaviorTreeState::*};
+
+    #[test]
+    fn condition_completes_while_its_predicate_holds() {
+        let mut harness = TreeHarness::new(r#"Condition(Fact("ready"))"#);
+        let states = harness.run(&[MockModel::new().fact("ready"), MockModel::new()]);
+        assert_eq!(states, [Complete, Failed]);
+    }
+
+    #[test]
+    fn guard_interrupts_its_node_when_its_predicate_stops_holding() {
+        let mut harness = TreeHarness::new(
+            r#"Guard(Fact("ready"), Sequence([User(Leaf("a")), User(Leaf("b"))]))"#,
+        );
+        let tick = harness.tick(&MockModel::new().fact("ready").leaf("a", Complete));
+        assert_eq!(tick.state, Waiting);
+        let tick = harness.tick(&MockModel::new().leaf("a", Complete));
+        assert_eq!(tick.state, Failed);
+        assert!(tick.ran.is_empty());
+        // The interrupted sequence starts over.
+        let tick = harness.tick(&MockModel::new().fact("ready").leaf("a", Complete));
+        assert_eq!(tick.ran, ["a", "b"]);
+    }
+}
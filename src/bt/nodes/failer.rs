@@ -6,11 +6,11 @@ pub struct Failer<M, C> {
 }
 
 impl<M, C> Failer<M, C> {
-    pub fn new(node: Box<dyn BehaviorTree<Model = M, Controller = C> + Send + Sync>) -> Self {
-        Failer {
-            name: get_bt_id(),
-            node,
-        }
+    pub fn new(
+        name: String,
+        node: Box<dyn BehaviorTree<Model = M, Controller = C> + Send + Sync>,
+    ) -> Self {
+        Failer { name, node }
     }
 }
 
@@ -21,11 +21,19 @@ impl<M: 'static, C: 'static> BehaviorTree for Failer<M, C> {
         self: &mut Self,
         model: &Self::Model,
         controller: &mut Self::Controller,
+        blackboard: &mut Blackboard,
         gas: &mut Option<i32>,
         mut audit: &mut Option<&mut BehaviorTreeAudit>,
     ) -> BehaviorTreeState {
         audit.enter(self.get_name());
-        match self.node.resume_with(model, controller, gas, audit) {
+        if !use_gas(gas) {
+            audit.exit(self.get_name(), BehaviorTreeState::WaitingForGas);
+            return BehaviorTreeState::WaitingForGas;
+        }
+        match self
+            .node
+            .resume_with(model, controller, blackboard, gas, audit)
+        {
             BehaviorTreeState::Failed | BehaviorTreeState::Complete => {
                 audit.exit(self.get_name(), BehaviorTreeState::Failed);
                 return BehaviorTreeState::Failed;
@@ -45,4 +53,43 @@ impl<M: 'static, C: 'static> BehaviorTree for Failer<M, C> {
     fn get_name(self: &Self) -> &String {
         &self.name
     }
+
+    fn get_children(
+        self: &Self,
+    ) -> Vec<&(dyn BehaviorTree<Model = Self::Model, Controller = Self::Controller> + Send + Sync)>
+    {
+        vec![&*self.node]
+    }
+
+    fn get_children_mut(
+        self: &mut Self,
+    ) -> Vec<
+        &mut (dyn BehaviorTree<Model = Self::Model, Controller = Self::Controller> + Send + Sync),
+    > {
+        vec![&mut *self.node]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::bt::{testing::*, BehaviorTreeState::*};
+
+    #[test]
+    fn fails_whatever_its_node_finishes_with() {
+        let mut harness = TreeHarness::new(r#"Failer(User(Leaf("a")))"#);
+        let states = harness.run(&[
+            MockModel::new().leaf("a", Complete),
+            MockModel::new().leaf("a", Failed),
+            MockModel::new(),
+        ]);
+        assert_eq!(states, [Failed, Failed, Waiting]);
+    }
+
+    #[test]
+    fn still_runs_its_node() {
+        let mut harness = TreeHarness::new(r#"Failer(User(Leaf("a")))"#);
+        let tick = harness.tick(&MockModel::new().leaf("a", Complete));
+        assert_eq!(tick.ran, ["a"]);
+        assert_eq!(tick.exit_of("a"), Some(Complete));
+    }
 }
@@ -6,11 +6,11 @@ pub struct Inverter<M, C> {
 }
 
 impl<M, C> Inverter<M, C> {
-    pub fn new(node: Box<dyn BehaviorTree<Model = M, Controller = C> + Send + Sync>) -> Self {
-        Inverter {
-            name: get_bt_id(),
-            node,
-        }
+    pub fn new(
+        name: String,
+        node: Box<dyn BehaviorTree<Model = M, Controller = C> + Send + Sync>,
+    ) -> Self {
+        Inverter { name, node }
     }
 }
 
@@ -21,11 +21,19 @@ impl<M: 'static, C: 'static> BehaviorTree for Inverter<M, C> {
         self: &mut Self,
         model: &Self::Model,
         controller: &mut Self::Controller,
+        blackboard: &mut Blackboard,
         gas: &mut Option<i32>,
         mut audit: &mut Option<&mut BehaviorTreeAudit>,
     ) -> BehaviorTreeState {
         audit.enter(self.get_name());
-        match self.node.resume_with(model, controller, gas, audit) {
+        if !use_gas(gas) {
+            audit.exit(self.get_name(), BehaviorTreeState::WaitingForGas);
+            return BehaviorTreeState::WaitingForGas;
+        }
+        match self
+            .node
+            .resume_with(model, controller, blackboard, gas, audit)
+        {
             BehaviorTreeState::Complete => {
                 audit.exit(self.get_name(), BehaviorTreeState::Failed);
                 return BehaviorTreeState::Failed;
@@ -49,4 +57,42 @@ impl<M: 'static, C: 'static> BehaviorTree for Inverter<M, C> {
     fn get_name(self: &Self) -> &String {
         &self.name
     }
+
+    fn get_children(
+        self: &Self,
+    ) -> Vec<&(dyn BehaviorTree<Model = Self::Model, Controller = Self::Controller> + Send + Sync)>
+    {
+        vec![&*self.node]
+    }
+
+    fn get_children_mut(
+        self: &mut Self,
+    ) -> Vec<
+        &mut (dyn BehaviorTree<Model = Self::Model, Controller = Self::Controller> + Send + Sync),
+    > {
+        vec![&mut *self.node]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::bt::{testing::*, BehaviorTreeState::*};
+
+    #[test]
+    fn swaps_complete_and_failed() {
+        let mut harness = TreeHarness::new(r#"Inverter(User(Leaf("a")))"#);
+        let states = harness.run(&[
+            MockModel::new().leaf("a", Complete),
+            MockModel::new().leaf("a", Failed),
+        ]);
+        assert_eq!(states, [Failed, Complete]);
+    }
+
+    #[test]
+    fn passes_on_waiting() {
+        let mut harness = TreeHarness::new(r#"Inverter(User(Leaf("a")))"#);
+        assert_eq!(harness.tick(&MockModel::new()).state, Waiting);
+        harness.gas = Some(0);
+        assert_eq!(harness.tick(&MockModel::new()).state, WaitingForGas);
+    }
 }
@@ -1,28 +1,46 @@
+mod blackboard;
+mod condition;
 mod failer;
 mod inverter;
+mod parallel;
+mod random;
+mod reactive;
 mod repeat;
 mod selector;
 mod sequence;
 mod succeeder;
+mod timing;
+mod utility;
 
+pub use blackboard::*;
+pub use condition::*;
 pub use failer::*;
 pub use inverter::*;
+pub use parallel::*;
+pub use random::*;
+pub use reactive::*;
 pub use repeat::*;
 pub use selector::*;
 pub use sequence::*;
 pub use succeeder::*;
+pub use timing::*;
+pub use utility::*;
 
-use serde::Serialize;
-use std::sync::atomic::Ordering;
+use serde::{Deserialize, Serialize};
 
-#[derive(Serialize, Clone, Debug)]
+use super::{
+    snapshot::{migrate_states, restore_states, save_states},
+    BehaviorTreeNodeState, BehaviorTreeRestoreError, BehaviorTreeSnapshot, Blackboard,
+};
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
 pub enum BehaviorTreeMarker {
     Enter(String),
     Marker(String),
     Exit(String, BehaviorTreeState),
 }
 
-#[derive(Serialize, Clone, Default, Debug)]
+#[derive(Serialize, Deserialize, Clone, Default, Debug)]
 pub struct BehaviorTreeAudit {
     events: Vec<BehaviorTreeMarker>,
     place: Vec<String>,
@@ -36,6 +54,16 @@ pub trait BehaviorTreeAuditTrait {
     fn exit(&mut self, node_name: &String, state: BehaviorTreeState);
 }
 
+impl BehaviorTreeAudit {
+    pub fn get_events(&self) -> &Vec<BehaviorTreeMarker> {
+        &self.events
+    }
+
+    pub fn get_place(&self) -> &Vec<String> {
+        &self.place
+    }
+}
+
 impl BehaviorTreeAuditTrait for BehaviorTreeAudit {
     fn enter(&mut self, node_name: &String) {
         self.events
@@ -82,7 +110,7 @@ where
     }
 }
 
-#[derive(Serialize, Debug, PartialEq, Copy, Clone)]
+#[derive(Serialize, Deserialize, Debug, PartialEq, Copy, Clone)]
 pub enum BehaviorTreeState {
     Waiting,
     // The powered function could not continue, due to lack of gas.
@@ -93,12 +121,49 @@ pub enum BehaviorTreeState {
     Complete,
 }
 
-lazy_static! {
-    pub static ref DEFAULT_IDS: std::sync::atomic::AtomicU32 = std::sync::atomic::AtomicU32::new(0);
+// Spends one step of gas. Without a gas limit, every step is free.
+pub fn use_gas(gas: &mut Option<i32>) -> bool {
+    match gas {
+        Some(remaining) if *remaining <= 0 => false,
+        Some(remaining) => {
+            *remaining -= 1;
+            true
+        }
+        None => true,
+    }
 }
 
-pub fn get_bt_id() -> String {
-    format!("<node {}>", DEFAULT_IDS.fetch_add(1, Ordering::SeqCst))
+// Shares a fixed amount of gas between many trees each frame. Whichever tree ran out of gas
+// goes first on the next frame, so no tree is starved for long.
+pub struct GasBudget {
+    pub gas_per_frame: i32,
+    first: usize,
+}
+
+impl GasBudget {
+    pub fn new(gas_per_frame: i32) -> Self {
+        GasBudget {
+            gas_per_frame,
+            first: 0,
+        }
+    }
+
+    pub fn get_gas(&self) -> Option<i32> {
+        Some(self.gas_per_frame)
+    }
+
+    pub fn get_order(&self, tree_count: usize) -> impl Iterator<Item = usize> {
+        let first = if tree_count > 0 {
+            self.first % tree_count
+        } else {
+            0
+        };
+        (first..tree_count).chain(0..first)
+    }
+
+    pub fn set_starved(&mut self, starved: Option<usize>) {
+        self.first = starved.unwrap_or(0);
+    }
 }
 
 pub trait BehaviorTree {
@@ -110,9 +175,70 @@ pub trait BehaviorTree {
         self: &mut Self,
         model: &Self::Model,
         controller: &mut Self::Controller,
+        blackboard: &mut Blackboard,
         gas: &mut Option<i32>,
         audit: &mut Option<&mut BehaviorTreeAudit>,
     ) -> BehaviorTreeState;
 
     fn reset(self: &mut Self, model: &Self::Model);
+
+    // For debugging, only composites and decorators have children.
+    fn get_children(
+        self: &Self,
+    ) -> Vec<&(dyn BehaviorTree<Model = Self::Model, Controller = Self::Controller> + Send + Sync)>
+    {
+        Vec::new()
+    }
+
+    // For snapshots, like get_children.
+    fn get_children_mut(
+        self: &mut Self,
+    ) -> Vec<
+        &mut (dyn BehaviorTree<Model = Self::Model, Controller = Self::Controller> + Send + Sync),
+    > {
+        Vec::new()
+    }
+
+    // The node's own running state, without its children's. Nodes that keep none return None.
+    fn save_state(self: &Self) -> Option<BehaviorTreeNodeState> {
+        None
+    }
+
+    // Puts back state from save_state, or fails without changing anything if it doesn't fit.
+    fn load_state(self: &mut Self, _state: &BehaviorTreeNodeState) -> Result<(), String> {
+        Ok(())
+    }
+
+    // Where the whole tree is in its run.
+    fn snapshot(self: &Self) -> BehaviorTreeSnapshot {
+        let mut snapshot = BehaviorTreeSnapshot::default();
+        save_states(self, &mut snapshot);
+        snapshot
+    }
+
+    // Puts the tree back where a snapshot of a tree built from the same definition was.
+    fn restore(
+        self: &mut Self,
+        snapshot: &BehaviorTreeSnapshot,
+    ) -> Result<(), Vec<BehaviorTreeRestoreError>> {
+        restore_states(self, snapshot)
+    }
+
+    // Takes over the running state of the nodes an old tree shares with this one, so a tree
+    // swapped for a new version of itself, like on an upgrade, carries on instead of starting
+    // over. Nodes match by id and type. Returns the nodes that found no state that fits them,
+    // which start fresh.
+    fn migrate_from(
+        self: &mut Self,
+        old: &(dyn BehaviorTree<Model = Self::Model, Controller = Self::Controller> + Send + Sync),
+    ) -> Vec<String> {
+        migrate_states(self, old)
+    }
+
+    // For debugging, the short name of the node's type, without module path or generics.
+    fn get_type_name(self: &Self) -> &'static str {
+        let type_name = std::any::type_name::<Self>();
+        let type_name = type_name.split('<').next().unwrap_or(type_name);
+        type_name.rsplit("::").next().unwrap_or(type_name)
+    }
 }
@@ -0,0 +1,253 @@
+use serde::{Deserialize, Serialize};
+
+use crate::bt::*;
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Copy, Clone)]
+pub enum ParallelPolicy {
+    RequireAll,
+    RequireOne,
+    Threshold(usize),
+}
+
+impl ParallelPolicy {
+    fn is_met(&self, count: usize, children: usize) -> bool {
+        match self {
+            ParallelPolicy::RequireAll => count >= children,
+            ParallelPolicy::RequireOne => count >= 1,
+            ParallelPolicy::Threshold(threshold) => count >= *threshold,
+        }
+    }
+}
+
+pub struct Parallel<M, C> {
+    name: String,
+    nodes: Vec<Box<dyn BehaviorTree<Model = M, Controller = C> + Send + Sync>>,
+    success_policy: ParallelPolicy,
+    failure_policy: ParallelPolicy,
+    results: Vec<Option<BehaviorTreeState>>,
+}
+
+impl<M, C> Parallel<M, C> {
+    pub fn new(
+        name: String,
+        nodes: Vec<Box<dyn BehaviorTree<Model = M, Controller = C> + Send + Sync>>,
+        success_policy: ParallelPolicy,
+        failure_policy: ParallelPolicy,
+    ) -> Self {
+        let results = vec![None; nodes.len()];
+        Parallel {
+            name,
+            nodes,
+            success_policy,
+            failure_policy,
+            results,
+        }
+    }
+}
+
+impl<M: 'static, C: 'static> Parallel<M, C> {
+    fn finish(&mut self, model: &M, result: BehaviorTreeState) -> BehaviorTreeState {
+        for node in self.nodes.iter_mut() {
+            node.reset(model);
+        }
+        for node_result in self.results.iter_mut() {
+            *node_result = None;
+        }
+        result
+    }
+}
+
+impl<M: 'static, C: 'static> BehaviorTree for Parallel<M, C> {
+    type Model = M;
+    type Controller = C;
+    fn resume_with(
+        self: &mut Self,
+        model: &Self::Model,
+        controller: &mut Self::Controller,
+        blackboard: &mut Blackboard,
+        gas: &mut Option<i32>,
+        mut audit: &mut Option<&mut BehaviorTreeAudit>,
+    ) -> BehaviorTreeState {
+        audit.enter(self.get_name());
+        if !use_gas(gas) {
+            audit.exit(self.get_name(), BehaviorTreeState::WaitingForGas);
+            return BehaviorTreeState::WaitingForGas;
+        }
+        let mut waiting_result = BehaviorTreeState::Waiting;
+        for (node, node_result) in self.nodes.iter_mut().zip(self.results.iter_mut()) {
+            if node_result.is_some() {
+                // Already finished, waiting on its siblings.
+                continue;
+            }
+            match node.resume_with(model, controller, blackboard, gas, audit) {
+                BehaviorTreeState::WaitingForGas => {
+                    // Out of gas, the remaining nodes get their turn next time.
+                    waiting_result = BehaviorTreeState::WaitingForGas;
+                    break;
+                }
+                BehaviorTreeState::Waiting => {}
+                result => {
+                    *node_result = Some(result);
+                }
+            }
+        }
+        let children = self.nodes.len();
+        let completed = self
+            .results
+            .iter()
+            .filter(|result| **result == Some(BehaviorTreeState::Complete))
+            .count();
+        let failed = self
+            .results
+            .iter()
+            .filter(|result| **result == Some(BehaviorTreeState::Failed))
+            .count();
+        let result = if self.success_policy.is_met(completed, children) {
+            self.finish(model, BehaviorTreeState::Complete)
+        } else if self.failure_policy.is_met(failed, children) {
+            self.finish(model, BehaviorTreeState::Failed)
+        } else if completed + failed == children {
+            // Everything finished without satisfying either policy.
+            self.finish(model, BehaviorTreeState::Failed)
+        } else {
+            waiting_result
+        };
+        audit.exit(self.get_name(), result);
+        result
+    }
+
+    fn reset(self: &mut Self, model: &Self::Model) {
+        self.finish(model, BehaviorTreeState::Waiting);
+    }
+
+    fn get_name(self: &Self) -> &String {
+        &self.name
+    }
+
+    fn get_children(
+        self: &Self,
+    ) -> Vec<&(dyn BehaviorTree<Model = Self::Model, Controller = Self::Controller> + Send + Sync)>
+    {
+        self.nodes.iter().map(|node| &**node).collect()
+    }
+
+    fn get_children_mut(
+        self: &mut Self,
+    ) -> Vec<
+        &mut (dyn BehaviorTree<Model = Self::Model, Controller = Self::Controller> + Send + Sync),
+    > {
+        self.nodes.iter_mut().map(|node| &mut **node as _).collect()
+    }
+
+    fn save_state(self: &Self) -> Option<BehaviorTreeNodeState> {
+        to_node_state(&self.results)
+    }
+
+    fn load_state(self: &mut Self, state: &BehaviorTreeNodeState) -> Result<(), String> {
+        let results: Vec<Option<BehaviorTreeState>> = from_node_state(state)?;
+        if results.len() != self.nodes.len() {
+            return Err(format!(
+                "{} results for {} nodes",
+                results.len(),
+                self.nodes.len()
+            ));
+        }
+        self.results = results;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::bt::{testing::*, BehaviorTreeState::*};
+
+    fn parallel(success_policy: &str, failure_policy: &str) -> TreeHarness {
+        TreeHarness::new(&format!(
+            r#"Parallel([User(Leaf("a")), User(Leaf("b")), User(Leaf("c"))], {}, {})"#,
+            success_policy, failure_policy
+        ))
+    }
+
+    #[test]
+    fn require_all_waits_for_every_node() {
+        let mut harness = parallel("RequireAll", "RequireAll");
+        let tick = harness.tick(&MockModel::new().leaf("a", Complete).leaf("c", Complete));
+        assert_eq!(tick.state, Waiting);
+        assert_eq!(tick.ran, ["a", "b", "c"]);
+        // Finished nodes aren't run again while their siblings catch up.
+        let tick = harness.tick(&MockModel::new().leaf("b", Complete));
+        assert_eq!(tick.state, Complete);
+        assert_eq!(tick.ran, ["b"]);
+    }
+
+    #[test]
+    fn require_one_finishes_at_the_first_result() {
+        let mut harness = parallel("RequireOne", "RequireAll");
+        let tick = harness.tick(&MockModel::new().leaf("b", Complete));
+        assert_eq!(tick.state, Complete);
+        assert_eq!(tick.ran, ["a", "b", "c"]);
+        let mut harness = parallel("RequireAll", "RequireOne");
+        let tick = harness.tick(&MockModel::new().leaf("c", Failed));
+        assert_eq!(tick.state, Failed);
+    }
+
+    #[test]
+    fn threshold_counts_results() {
+        let mut harness = parallel("Threshold(2)", "Threshold(2)");
+        let tick = harness.tick(&MockModel::new().leaf("a", Complete).leaf("b", Failed));
+        assert_eq!(tick.state, Waiting);
+        let tick = harness.tick(&MockModel::new().leaf("c", Complete));
+        assert_eq!(tick.state, Complete);
+        assert_eq!(tick.ran, ["c"]);
+    }
+
+    #[test]
+    fn success_is_checked_before_failure() {
+        let mut harness = parallel("RequireOne", "RequireOne");
+        let tick = harness.tick(
+            &MockModel::new()
+                .leaf("a", Failed)
+                .leaf("b", Complete)
+                .leaf("c", Failed),
+        );
+        assert_eq!(tick.state, Complete);
+    }
+
+    #[test]
+    fn fails_when_every_node_finishes_without_meeting_a_policy() {
+        let mut harness = parallel("RequireAll", "Threshold(3)");
+        let tick = harness.tick(
+            &MockModel::new()
+                .leaf("a", Complete)
+                .leaf("b", Failed)
+                .leaf("c", Complete),
+        );
+        assert_eq!(tick.state, Failed);
+    }
+
+    #[test]
+    fn thieves_path_and_attack_together() {
+        // The shape of the thief's tree.
+        let tree = r#"Parallel(
+            [User(Leaf("path")), User(Leaf("attack"))],
+            RequireAll,
+            RequireOne,
+        )"#;
+        let mut harness = TreeHarness::new(tree);
+        let tick = harness.tick(
+            &MockModel::new()
+                .leaf("path", Complete)
+                .leaf("attack", Complete),
+        );
+        assert_eq!(tick.state, Complete);
+        assert_eq!(tick.ran, ["path", "attack"]);
+        // Stuck, but still attacking whatever is in the way.
+        let tick = harness.tick(
+            &MockModel::new()
+                .leaf("path", Failed)
+                .leaf("attack", Complete),
+        );
+        assert_eq!(tick.state, Failed);
+        assert_eq!(tick.ran, ["path", "attack"]);
+    }
+}
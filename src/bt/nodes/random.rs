@@ -0,0 +1,322 @@
+use rand::{seq::SliceRandom, Rng, SeedableRng};
+use rand_pcg::Pcg32;
+
+use crate::bt::*;
+
+// Seeds a node's generator from the tree's seed and the node's id, so sibling random nodes
+// draw different numbers but the same tree and seed always draw the same ones.
+pub fn node_seed(seed: u64, id: &str) -> u64 {
+    // FNV-1a, which unlike the std hasher is fixed across Rust versions.
+    id.bytes().fold(seed ^ 0xcbf29ce484222325, |hash, byte| {
+        (hash ^ byte as u64).wrapping_mul(0x100000001b3)
+    })
+}
+
+// Like Selector, but tries its nodes in a fresh random order each time it starts over.
+pub struct RandomSelector<M, C> {
+    name: String,
+    nodes: Vec<Box<dyn BehaviorTree<Model = M, Controller = C> + Send + Sync>>,
+    order: Vec<usize>,
+    index: Option<usize>,
+    rng: Pcg32,
+}
+
+impl<M, C> RandomSelector<M, C> {
+    pub fn new(
+        name: String,
+        nodes: Vec<Box<dyn BehaviorTree<Model = M, Controller = C> + Send + Sync>>,
+        seed: u64,
+    ) -> Self {
+        let order = (0..nodes.len()).collect();
+        RandomSelector {
+            name,
+            nodes,
+            order,
+            index: None,
+            rng: Pcg32::seed_from_u64(seed),
+        }
+    }
+}
+
+impl<M: 'static, C: 'static> BehaviorTree for RandomSelector<M, C> {
+    type Model = M;
+    type Controller = C;
+    fn resume_with(
+        self: &mut Self,
+        model: &Self::Model,
+        controller: &mut Self::Controller,
+        blackboard: &mut Blackboard,
+        gas: &mut Option<i32>,
+        mut audit: &mut Option<&mut BehaviorTreeAudit>,
+    ) -> BehaviorTreeState {
+        audit.enter(self.get_name());
+        if !use_gas(gas) {
+            audit.exit(self.get_name(), BehaviorTreeState::WaitingForGas);
+            return BehaviorTreeState::WaitingForGas;
+        }
+        let mut running_index = match self.index {
+            Some(running_index) => running_index,
+            None => {
+                self.order.shuffle(&mut self.rng);
+                0
+            }
+        };
+        loop {
+            if let Some(node_index) = self.order.get(running_index).copied() {
+                let result =
+                    self.nodes[node_index].resume_with(model, controller, blackboard, gas, audit);
+                match result {
+                    BehaviorTreeState::Failed => {
+                        // Move on to the next node.
+                        self.nodes[node_index].reset(model);
+                        running_index += 1;
+                    }
+                    BehaviorTreeState::Complete => {
+                        self.nodes[node_index].reset(model);
+                        self.index = None;
+                        audit.exit(self.get_name(), result);
+                        return result;
+                    }
+                    _ => {
+                        // Waiting, NeedsGas
+                        self.index = Some(running_index);
+                        audit.exit(self.get_name(), result);
+                        return result;
+                    }
+                }
+            } else {
+                self.index = None;
+                audit.exit(self.get_name(), BehaviorTreeState::Failed);
+                return BehaviorTreeState::Failed;
+            }
+        }
+    }
+
+    fn reset(self: &mut Self, model: &Self::Model) {
+        if let Some(running_index) = self.index.take() {
+            self.nodes[self.order[running_index]].reset(model);
+        }
+    }
+
+    fn get_name(self: &Self) -> &String {
+        &self.name
+    }
+
+    fn get_children(
+        self: &Self,
+    ) -> Vec<&(dyn BehaviorTree<Model = Self::Model, Controller = Self::Controller> + Send + Sync)>
+    {
+        self.nodes.iter().map(|node| &**node).collect()
+    }
+
+    fn get_children_mut(
+        self: &mut Self,
+    ) -> Vec<
+        &mut (dyn BehaviorTree<Model = Self::Model, Controller = Self::Controller> + Send + Sync),
+    > {
+        self.nodes.iter_mut().map(|node| &mut **node as _).collect()
+    }
+
+    fn save_state(self: &Self) -> Option<BehaviorTreeNodeState> {
+        to_node_state(&(&self.order, self.index, &self.rng))
+    }
+
+    fn load_state(self: &mut Self, state: &BehaviorTreeNodeState) -> Result<(), String> {
+        let (order, index, rng): (Vec<usize>, Option<usize>, Pcg32) = from_node_state(state)?;
+        let mut sorted = order.clone();
+        sorted.sort_unstable();
+        if !sorted.iter().copied().eq(0..self.nodes.len()) {
+            return Err(format!(
+                "{:?} is not an order of {} nodes",
+                order,
+                self.nodes.len()
+            ));
+        }
+        check_index(index, self.nodes.len())?;
+        self.order = order;
+        self.index = index;
+        self.rng = rng;
+        Ok(())
+    }
+}
+
+// Picks one node, with chances in proportion to the weights, and runs it to the end. The
+// picked node's result is the result. Nodes with no weight are never picked.
+pub struct WeightedRandom<M, C> {
+    name: String,
+    nodes: Vec<(
+        f32,
+        Box<dyn BehaviorTree<Model = M, Controller = C> + Send + Sync>,
+    )>,
+    index: Option<usize>,
+    rng: Pcg32,
+}
+
+impl<M, C> WeightedRandom<M, C> {
+    pub fn new(
+        name: String,
+        nodes: Vec<(
+            f32,
+            Box<dyn BehaviorTree<Model = M, Controller = C> + Send + Sync>,
+        )>,
+        seed: u64,
+    ) -> Self {
+        WeightedRandom {
+            name,
+            nodes,
+            index: None,
+            rng: Pcg32::seed_from_u64(seed),
+        }
+    }
+
+    fn choose(self: &mut Self) -> Option<usize> {
+        // Weights that validation rejects are never picked rather than poisoning the total,
+        // which is summed wide so large weights can't overflow it.
+        let weight_of = |weight: f32| {
+            if weight.is_finite() && weight > 0. {
+                weight as f64
+            } else {
+                0.
+            }
+        };
+        let total: f64 = self
+            .nodes
+            .iter()
+            .map(|(weight, _)| weight_of(*weight))
+            .sum();
+        if total <= 0. {
+            return None;
+        }
+        let mut roll = self.rng.gen_range(0. ..total);
+        let mut chosen = None;
+        for (index, (weight, _)) in self.nodes.iter().enumerate() {
+            let weight = weight_of(*weight);
+            if weight <= 0. {
+                continue;
+            }
+            chosen = Some(index);
+            if roll < weight {
+                break;
+            }
+            roll -= weight;
+        }
+        // Rounding can leave the roll just past the end, in which case the last node wins.
+        chosen
+    }
+}
+
+impl<M: 'static, C: 'static> BehaviorTree for WeightedRandom<M, C> {
+    type Model = M;
+    type Controller = C;
+    fn resume_with(
+        self: &mut Self,
+        model: &Self::Model,
+        controller: &mut Self::Controller,
+        blackboard: &mut Blackboard,
+        gas: &mut Option<i32>,
+        mut audit: &mut Option<&mut BehaviorTreeAudit>,
+    ) -> BehaviorTreeState {
+        audit.enter(self.get_name());
+        if !use_gas(gas) {
+            audit.exit(self.get_name(), BehaviorTreeState::WaitingForGas);
+            return BehaviorTreeState::WaitingForGas;
+        }
+        let chosen = match self.index.or_else(|| self.choose()) {
+            Some(chosen) => chosen,
+            None => {
+                audit.exit(self.get_name(), BehaviorTreeState::Failed);
+                return BehaviorTreeState::Failed;
+            }
+        };
+        let result = self.nodes[chosen]
+            .1
+            .resume_with(model, controller, blackboard, gas, audit);
+        match result {
+            BehaviorTreeState::Complete | BehaviorTreeState::Failed => {
+                self.nodes[chosen].1.reset(model);
+                self.index = None;
+            }
+            _ => {
+                // Waiting, NeedsGas
+                self.index = Some(chosen);
+            }
+        }
+        audit.exit(self.get_name(), result);
+        result
+    }
+
+    fn reset(self: &mut Self, model: &Self::Model) {
+        if let Some(index) = self.index.take() {
+            self.nodes[index].1.reset(model);
+        }
+    }
+
+    fn get_name(self: &Self) -> &String {
+        &self.name
+    }
+
+    fn get_children(
+        self: &Self,
+    ) -> Vec<&(dyn BehaviorTree<Model = Self::Model, Controller = Self::Controller> + Send + Sync)>
+    {
+        self.nodes.iter().map(|(_, node)| &**node).collect()
+    }
+
+    fn get_children_mut(
+        self: &mut Self,
+    ) -> Vec<
+        &mut (dyn BehaviorTree<Model = Self::Model, Controller = Self::Controller> + Send + Sync),
+    > {
+        self.nodes
+            .iter_mut()
+            .map(|(_, node)| &mut **node as _)
+            .collect()
+    }
+
+    fn save_state(self: &Self) -> Option<BehaviorTreeNodeState> {
+        to_node_state(&(self.index, &self.rng))
+    }
+
+    fn load_state(self: &mut Self, state: &BehaviorTreeNodeState) -> Result<(), String> {
+        let (index, rng) = from_node_state(state)?;
+        check_index(index, self.nodes.len())?;
+        self.index = index;
+        self.rng = rng;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bt::testing::*;
+
+    fn weighted(weights: &[f32]) -> WeightedRandom<MockModel, MockController> {
+        let nodes = weights
+            .iter()
+            .enumerate()
+            .map(|(index, weight)| {
+                let leaf: MockTree = Box::new(MockLeafNode(index.to_string()));
+                (*weight, leaf)
+            })
+            .collect();
+        WeightedRandom::new("weighted".to_string(), nodes, 0)
+    }
+
+    #[test]
+    fn odd_weights_are_never_picked() {
+        let mut node = weighted(&[f32::INFINITY, f32::NAN, -1., 1.]);
+        for _ in 0..100 {
+            assert_eq!(node.choose(), Some(3));
+        }
+        assert_eq!(weighted(&[f32::NAN, f32::NEG_INFINITY]).choose(), None);
+    }
+
+    #[test]
+    fn huge_weights_do_not_overflow() {
+        let mut node = weighted(&[f32::MAX, f32::MAX]);
+        for _ in 0..100 {
+            assert!(node.choose().is_some());
+        }
+    }
+}
@@ -0,0 +1,291 @@
+use crate::bt::*;
+
+// Like Selector, but starts from the first node on every tick, so a higher priority node can
+// take over from a lower priority one that is still running. The interrupted node is reset.
+pub struct ReactiveSelector<M, C> {
+    name: String,
+    nodes: Vec<Box<dyn BehaviorTree<Model = M, Controller = C> + Send + Sync>>,
+    index: Option<usize>,
+}
+
+impl<M, C> ReactiveSelector<M, C> {
+    pub fn new(
+        name: String,
+        nodes: Vec<Box<dyn BehaviorTree<Model = M, Controller = C> + Send + Sync>>,
+    ) -> Self {
+        ReactiveSelector {
+            name,
+            nodes,
+            index: None,
+        }
+    }
+}
+
+impl<M: 'static, C: 'static> BehaviorTree for ReactiveSelector<M, C> {
+    type Model = M;
+    type Controller = C;
+    fn resume_with(
+        self: &mut Self,
+        model: &Self::Model,
+        controller: &mut Self::Controller,
+        blackboard: &mut Blackboard,
+        gas: &mut Option<i32>,
+        mut audit: &mut Option<&mut BehaviorTreeAudit>,
+    ) -> BehaviorTreeState {
+        audit.enter(self.get_name());
+        if !use_gas(gas) {
+            audit.exit(self.get_name(), BehaviorTreeState::WaitingForGas);
+            return BehaviorTreeState::WaitingForGas;
+        }
+        for running_index in 0..self.nodes.len() {
+            let result =
+                self.nodes[running_index].resume_with(model, controller, blackboard, gas, audit);
+            match result {
+                BehaviorTreeState::Failed => {
+                    // Move on to the next node, starting this one over next time.
+                    self.nodes[running_index].reset(model);
+                }
+                BehaviorTreeState::WaitingForGas => {
+                    // Can't tell yet whether this node takes over.
+                    audit.exit(self.get_name(), result);
+                    return result;
+                }
+                _ => {
+                    // Complete, Waiting
+                    preempt(&mut self.nodes, &mut self.index, running_index, model);
+                    if result == BehaviorTreeState::Complete {
+                        self.nodes[running_index].reset(model);
+                        self.index = None;
+                    }
+                    audit.exit(self.get_name(), result);
+                    return result;
+                }
+            }
+        }
+        // The running node was reached too, so it has already failed and been reset.
+        self.index = None;
+        audit.exit(self.get_name(), BehaviorTreeState::Failed);
+        BehaviorTreeState::Failed
+    }
+
+    fn reset(self: &mut Self, model: &Self::Model) {
+        if let Some(index) = self.index.take() {
+            self.nodes[index].reset(model);
+        }
+    }
+
+    fn get_name(self: &Self) -> &String {
+        &self.name
+    }
+
+    fn get_children(
+        self: &Self,
+    ) -> Vec<&(dyn BehaviorTree<Model = Self::Model, Controller = Self::Controller> + Send + Sync)>
+    {
+        self.nodes.iter().map(|node| &**node).collect()
+    }
+
+    fn get_children_mut(
+        self: &mut Self,
+    ) -> Vec<
+        &mut (dyn BehaviorTree<Model = Self::Model, Controller = Self::Controller> + Send + Sync),
+    > {
+        self.nodes.iter_mut().map(|node| &mut **node as _).collect()
+    }
+
+    fn save_state(self: &Self) -> Option<BehaviorTreeNodeState> {
+        to_node_state(&self.index)
+    }
+
+    fn load_state(self: &mut Self, state: &BehaviorTreeNodeState) -> Result<(), String> {
+        let index = from_node_state(state)?;
+        check_index(index, self.nodes.len())?;
+        self.index = index;
+        Ok(())
+    }
+}
+
+// Like Sequence, but starts from the first node on every tick, so an earlier node that stops
+// completing interrupts the one still running. The interrupted node is reset.
+pub struct ReactiveSequence<M, C> {
+    name: String,
+    nodes: Vec<Box<dyn BehaviorTree<Model = M, Controller = C> + Send + Sync>>,
+    index: Option<usize>,
+}
+
+impl<M, C> ReactiveSequence<M, C> {
+    pub fn new(
+        name: String,
+        nodes: Vec<Box<dyn BehaviorTree<Model = M, Controller = C> + Send + Sync>>,
+    ) -> Self {
+        ReactiveSequence {
+            name,
+            nodes,
+            index: None,
+        }
+    }
+}
+
+impl<M: 'static, C: 'static> BehaviorTree for ReactiveSequence<M, C> {
+    type Model = M;
+    type Controller = C;
+    fn resume_with(
+        self: &mut Self,
+        model: &Self::Model,
+        controller: &mut Self::Controller,
+        blackboard: &mut Blackboard,
+        gas: &mut Option<i32>,
+        mut audit: &mut Option<&mut BehaviorTreeAudit>,
+    ) -> BehaviorTreeState {
+        audit.enter(self.get_name());
+        if !use_gas(gas) {
+            audit.exit(self.get_name(), BehaviorTreeState::WaitingForGas);
+            return BehaviorTreeState::WaitingForGas;
+        }
+        for running_index in 0..self.nodes.len() {
+            let result =
+                self.nodes[running_index].resume_with(model, controller, blackboard, gas, audit);
+            match result {
+                BehaviorTreeState::Complete => {
+                    // Move on to the next node, starting this one over next time.
+                    self.nodes[running_index].reset(model);
+                }
+                BehaviorTreeState::WaitingForGas => {
+                    audit.exit(self.get_name(), result);
+                    return result;
+                }
+                _ => {
+                    // Failed, Waiting
+                    preempt(&mut self.nodes, &mut self.index, running_index, model);
+                    if result == BehaviorTreeState::Failed {
+                        self.nodes[running_index].reset(model);
+                        self.index = None;
+                    }
+                    audit.exit(self.get_name(), result);
+                    return result;
+                }
+            }
+        }
+        self.index = None;
+        audit.exit(self.get_name(), BehaviorTreeState::Complete);
+        BehaviorTreeState::Complete
+    }
+
+    fn reset(self: &mut Self, model: &Self::Model) {
+        if let Some(index) = self.index.take() {
+            self.nodes[index].reset(model);
+        }
+    }
+
+    fn get_name(self: &Self) -> &String {
+        &self.name
+    }
+
+    fn get_children(
+        self: &Self,
+    ) -> Vec<&(dyn BehaviorTree<Model = Self::Model, Controller = Self::Controller> + Send + Sync)>
+    {
+        self.nodes.iter().map(|node| &**node).collect()
+    }
+
+    fn get_children_mut(
+        self: &mut Self,
+    ) -> Vec<
+        &mut (dyn BehaviorTree<Model = Self::Model, Controller = Self::Controller> + Send + Sync),
+    > {
+        self.nodes.iter_mut().map(|node| &mut **node as _).collect()
+    }
+
+    fn save_state(self: &Self) -> Option<BehaviorTreeNodeState> {
+        to_node_state(&self.index)
+    }
+
+    fn load_state(self: &mut Self, state: &BehaviorTreeNodeState) -> Result<(), String> {
+        let index = from_node_state(state)?;
+        check_index(index, self.nodes.len())?;
+        self.index = index;
+        Ok(())
+    }
+}
+
+// Makes `running_index` the running node, resetting whichever node it took over from.
+fn preempt<M: 'static, C: 'static>(
+    nodes: &mut [Box<dyn BehaviorTree<Model = M, Controller = C> + Send + Sync>],
+    index: &mut Option<usize>,
+    running_index: usize,
+    model: &M,
+) {
+    if let Some(interrupted) = *index {
+        if interrupted != running_index {
+            nodes[interrupted].reset(model);
+        }
+    }
+    *index = Some(running_index);
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::bt::{testing::*, BehaviorTreeState::*};
+
+    // The Sequences show their resets by starting over from their first node.
+    const SELECTOR: &str = r#"ReactiveSelector([
+        User(Leaf("urgent")),
+        Sequence([User(Leaf("b1")), User(Leaf("b2"))]),
+    ])"#;
+
+    const SEQUENCE: &str = r#"ReactiveSequence([
+        Condition(Fact("safe")),
+        Sequence([User(Leaf("b1")), User(Leaf("b2"))]),
+    ])"#;
+
+    #[test]
+    fn selector_resumes_the_running_node() {
+        let mut harness = TreeHarness::new(SELECTOR);
+        let tick = harness.tick(&MockModel::new().leaf("urgent", Failed).leaf("b1", Complete));
+        assert_eq!(tick.state, Waiting);
+        assert_eq!(tick.ran, ["urgent", "b1", "b2"]);
+        let tick = harness.tick(&MockModel::new().leaf("urgent", Failed).leaf("b2", Complete));
+        assert_eq!(tick.state, Complete);
+        assert_eq!(tick.ran, ["urgent", "b2"]);
+    }
+
+    #[test]
+    fn selector_preempts_and_resets_the_running_node() {
+        let mut harness = TreeHarness::new(SELECTOR);
+        harness.tick(&MockModel::new().leaf("urgent", Failed).leaf("b1", Complete));
+        let tick = harness.tick(&MockModel::new());
+        assert_eq!(tick.state, Waiting);
+        assert_eq!(tick.ran, ["urgent"]);
+        assert_eq!(
+            tick.exits(),
+            [
+                ("urgent".to_string(), Waiting),
+                ("root".to_string(), Waiting)
+            ]
+        );
+        let tick = harness.tick(&MockModel::new().leaf("urgent", Failed).leaf("b1", Complete));
+        assert_eq!(tick.ran, ["urgent", "b1", "b2"]);
+    }
+
+    #[test]
+    fn sequence_resumes_the_running_node() {
+        let mut harness = TreeHarness::new(SEQUENCE);
+        let tick = harness.tick(&MockModel::new().fact("safe").leaf("b1", Complete));
+        assert_eq!(tick.state, Waiting);
+        assert_eq!(tick.ran, ["b1", "b2"]);
+        let tick = harness.tick(&MockModel::new().fact("safe").leaf("b2", Complete));
+        assert_eq!(tick.state, Complete);
+        assert_eq!(tick.ran, ["b2"]);
+    }
+
+    #[test]
+    fn sequence_interrupts_and_resets_the_running_node() {
+        let mut harness = TreeHarness::new(SEQUENCE);
+        harness.tick(&MockModel::new().fact("safe").leaf("b1", Complete));
+        let tick = harness.tick(&MockModel::new());
+        assert_eq!(tick.state, Failed);
+        assert!(tick.ran.is_empty());
+        let tick = harness.tick(&MockModel::new().fact("safe").leaf("b1", Complete));
+        assert_eq!(tick.ran, ["b1", "b2"]);
+    }
+}
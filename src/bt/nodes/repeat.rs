@@ -1,3 +1,5 @@
+use bevy::log::warn;
+
 use crate::bt::*;
 
 pub struct Repeat<M, C> {
@@ -5,18 +7,25 @@ pub struct Repeat<M, C> {
     node: Box<dyn BehaviorTree<Model = M, Controller = C> + Send + Sync>,
     runs: usize,
     runs_left: usize,
+    max_repeats_per_tick: usize,
+    // Whether the repeat limit has been logged since the last reset.
+    warned: bool,
 }
 
 impl<M, C> Repeat<M, C> {
     pub fn new(
+        name: String,
         node: Box<dyn BehaviorTree<Model = M, Controller = C> + Send + Sync>,
         runs: usize,
+        max_repeats_per_tick: usize,
     ) -> Self {
         Repeat {
-            name: get_bt_id(),
+            name,
             node,
             runs,
             runs_left: runs,
+            max_repeats_per_tick,
+            warned: false,
         }
     }
 }
@@ -28,12 +37,31 @@ impl<M: 'static, C: 'static> BehaviorTree for Repeat<M, C> {
         self: &mut Self,
         model: &Self::Model,
         controller: &mut Self::Controller,
+        blackboard: &mut Blackboard,
         gas: &mut Option<i32>,
         mut audit: &mut Option<&mut BehaviorTreeAudit>,
     ) -> BehaviorTreeState {
         audit.enter(self.get_name());
+        if !use_gas(gas) {
+            audit.exit(self.get_name(), BehaviorTreeState::WaitingForGas);
+            return BehaviorTreeState::WaitingForGas;
+        }
+        let mut repeats = 0;
         while self.runs_left > 0 {
-            let result = self.node.resume_with(model, controller, gas, audit);
+            if repeat_limit_hit(
+                &self.name,
+                repeats,
+                self.max_repeats_per_tick,
+                &mut self.warned,
+                audit,
+            ) {
+                audit.exit(self.get_name(), BehaviorTreeState::Waiting);
+                return BehaviorTreeState::Waiting;
+            }
+            repeats += 1;
+            let result = self
+                .node
+                .resume_with(model, controller, blackboard, gas, audit);
             match result {
                 BehaviorTreeState::Failed => {
                     self.runs_left = self.runs;
@@ -57,22 +85,59 @@ impl<M: 'static, C: 'static> BehaviorTree for Repeat<M, C> {
 
     fn reset(self: &mut Self, model: &Self::Model) {
         self.runs_left = self.runs;
+        self.warned = false;
     }
 
     fn get_name(self: &Self) -> &String {
         &self.name
     }
+
+    fn get_children(
+        self: &Self,
+    ) -> Vec<&(dyn BehaviorTree<Model = Self::Model, Controller = Self::Controller> + Send + Sync)>
+    {
+        vec![&*self.node]
+    }
+
+    fn get_children_mut(
+        self: &mut Self,
+    ) -> Vec<
+        &mut (dyn BehaviorTree<Model = Self::Model, Controller = Self::Controller> + Send + Sync),
+    > {
+        vec![&mut *self.node]
+    }
+
+    fn save_state(self: &Self) -> Option<BehaviorTreeNodeState> {
+        to_node_state(&self.runs_left)
+    }
+
+    fn load_state(self: &mut Self, state: &BehaviorTreeNodeState) -> Result<(), String> {
+        let runs_left = from_node_state(state)?;
+        if runs_left == 0 || runs_left > self.runs {
+            return Err(format!("{} runs left of {}", runs_left, self.runs));
+        }
+        self.runs_left = runs_left;
+        Ok(())
+    }
 }
 pub struct RepeatUntilFail<M, C> {
     name: String,
     node: Box<dyn BehaviorTree<Model = M, Controller = C> + Send + Sync>,
+    max_repeats_per_tick: usize,
+    warned: bool,
 }
 
 impl<M, C> RepeatUntilFail<M, C> {
-    pub fn new(node: Box<dyn BehaviorTree<Model = M, Controller = C> + Send + Sync>) -> Self {
+    pub fn new(
+        name: String,
+        node: Box<dyn BehaviorTree<Model = M, Controller = C> + Send + Sync>,
+        max_repeats_per_tick: usize,
+    ) -> Self {
         RepeatUntilFail {
-            name: get_bt_id(),
+            name,
             node,
+            max_repeats_per_tick,
+            warned: false,
         }
     }
 }
@@ -84,12 +149,31 @@ impl<M: 'static, C: 'static> BehaviorTree for RepeatUntilFail<M, C> {
         self: &mut Self,
         model: &Self::Model,
         controller: &mut Self::Controller,
+        blackboard: &mut Blackboard,
         gas: &mut Option<i32>,
         mut audit: &mut Option<&mut BehaviorTreeAudit>,
     ) -> BehaviorTreeState {
         audit.enter(self.get_name());
+        if !use_gas(gas) {
+            audit.exit(self.get_name(), BehaviorTreeState::WaitingForGas);
+            return BehaviorTreeState::WaitingForGas;
+        }
+        let mut repeats = 0;
         loop {
-            let result = self.node.resume_with(model, controller, gas, audit);
+            if repeat_limit_hit(
+                &self.name,
+                repeats,
+                self.max_repeats_per_tick,
+                &mut self.warned,
+                audit,
+            ) {
+                audit.exit(self.get_name(), BehaviorTreeState::Waiting);
+                return BehaviorTreeState::Waiting;
+            }
+            repeats += 1;
+            let result = self
+                .node
+                .resume_with(model, controller, blackboard, gas, audit);
             match result {
                 BehaviorTreeState::Failed => {
                     audit.exit(self.get_name(), BehaviorTreeState::Complete);
@@ -109,24 +193,47 @@ impl<M: 'static, C: 'static> BehaviorTree for RepeatUntilFail<M, C> {
     }
 
     fn reset(self: &mut Self, model: &Self::Model) {
-        // Nothing to do.
+        self.warned = false;
     }
 
     fn get_name(self: &Self) -> &String {
         &self.name
     }
+
+    fn get_children(
+        self: &Self,
+    ) -> Vec<&(dyn BehaviorTree<Model = Self::Model, Controller = Self::Controller> + Send + Sync)>
+    {
+        vec![&*self.node]
+    }
+
+    fn get_children_mut(
+        self: &mut Self,
+    ) -> Vec<
+        &mut (dyn BehaviorTree<Model = Self::Model, Controller = Self::Controller> + Send + Sync),
+    > {
+        vec![&mut *self.node]
+    }
 }
 
 pub struct RepeatUntilSuccess<M, C> {
     name: String,
     node: Box<dyn BehaviorTree<Model = M, Controller = C> + Send + Sync>,
+    max_repeats_per_tick: usize,
+    warned: bool,
 }
 
 impl<M, C> RepeatUntilSuccess<M, C> {
-    pub fn new(node: Box<dyn BehaviorTree<Model = M, Controller = C> + Send + Sync>) -> Self {
+    pub fn new(
+        name: String,
+        node: Box<dyn BehaviorTree<Model = M, Controller = C> + Send + Sync>,
+        max_repeats_per_tick: usize,
+    ) -> Self {
         RepeatUntilSuccess {
-            name: get_bt_id(),
+            name,
             node,
+            max_repeats_per_tick,
+            warned: false,
         }
     }
 }
@@ -138,12 +245,31 @@ impl<M: 'static, C: 'static> BehaviorTree for RepeatUntilSuccess<M, C> {
         self: &mut Self,
         model: &Self::Model,
         controller: &mut Self::Controller,
+        blackboard: &mut Blackboard,
         gas: &mut Option<i32>,
         mut audit: &mut Option<&mut BehaviorTreeAudit>,
     ) -> BehaviorTreeState {
         audit.enter(self.get_name());
+        if !use_gas(gas) {
+            audit.exit(self.get_name(), BehaviorTreeState::WaitingForGas);
+            return BehaviorTreeState::WaitingForGas;
+        }
+        let mut repeats = 0;
         loop {
-            let result = self.node.resume_with(model, controller, gas, audit);
+            if repeat_limit_hit(
+                &self.name,
+                repeats,
+                self.max_repeats_per_tick,
+                &mut self.warned,
+                audit,
+            ) {
+                audit.exit(self.get_name(), BehaviorTreeState::Waiting);
+                return BehaviorTreeState::Waiting;
+            }
+            repeats += 1;
+            let result = self
+                .node
+                .resume_with(model, controller, blackboard, gas, audit);
             match result {
                 BehaviorTreeState::Complete => {
                     audit.exit(self.get_name(), BehaviorTreeState::Complete);
@@ -163,10 +289,164 @@ impl<M: 'static, C: 'static> BehaviorTree for RepeatUntilSuccess<M, C> {
     }
 
     fn reset(self: &mut Self, model: &Self::Model) {
-        // Nothing to do.
+        self.warned = false;
     }
 
     fn get_name(self: &Self) -> &String {
         &self.name
     }
+
+    fn get_children(
+        self: &Self,
+    ) -> Vec<&(dyn BehaviorTree<Model = Self::Model, Controller = Self::Controller> + Send + Sync)>
+    {
+        vec![&*self.node]
+    }
+
+    fn get_children_mut(
+        self: &mut Self,
+    ) -> Vec<
+        &mut (dyn BehaviorTree<Model = Self::Model, Controller = Self::Controller> + Send + Sync),
+    > {
+        vec![&mut *self.node]
+    }
+}
+
+// Whether a repeat node has run its node as often as it may this tick. If so, it should wait
+// and carry on next tick rather than hold up the frame. Every hit is marked, but a node stuck
+// in a loop only logs once until it's reset.
+fn repeat_limit_hit(
+    name: &String,
+    repeats: usize,
+    max_repeats_per_tick: usize,
+    warned: &mut bool,
+    mut audit: &mut Option<&mut BehaviorTreeAudit>,
+) -> bool {
+    if repeats < max_repeats_per_tick {
+        return false;
+    }
+    audit.mark(&"Repeat limit".to_string());
+    if !*warned {
+        *warned = true;
+        warn!(
+            "{} repeated its node {} times in one tick, so it waits for the next",
+            name, repeats
+        );
+    }
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::bt::{testing::*, BehaviorTreeSettings, BehaviorTreeState::*};
+
+    fn harness_with_limit(tree: &str, max_repeats_per_tick: usize) -> TreeHarness {
+        let settings = BehaviorTreeSettings {
+            max_repeats_per_tick,
+            ..Default::default()
+        };
+        TreeHarness::build(tree, "{}", &settings).unwrap()
+    }
+
+    #[test]
+    fn repeat_runs_its_node_that_many_times() {
+        let mut harness = TreeHarness::new(r#"Repeat(User(Leaf("a")), 3)"#);
+        let tick = harness.tick(&MockModel::new().leaf("a", Complete));
+        assert_eq!(tick.state, Complete);
+        assert_eq!(tick.ran, ["a", "a", "a"]);
+    }
+
+    #[test]
+    fn repeat_counts_runs_across_ticks() {
+        let mut harness =
+            TreeHarness::new(r#"Repeat(Sequence([User(Leaf("a")), User(Leaf("b"))]), 2)"#);
+        let tick = harness.tick(&MockModel::new().leaf("a", Complete).leaf("b", Complete));
+        assert_eq!(tick.state, Complete);
+        let tick = harness.tick(&MockModel::new().leaf("a", Complete));
+        assert_eq!(tick.state, Waiting);
+        // The first run picks up at "b", then the second runs in full.
+        let tick = harness.tick(&MockModel::new().leaf("a", Complete).leaf("b", Complete));
+        assert_eq!(tick.state, Complete);
+        assert_eq!(tick.ran, ["b", "a", "b"]);
+    }
+
+    #[test]
+    fn repeat_fails_and_starts_over_when_its_node_fails() {
+        let mut harness =
+            TreeHarness::new(r#"Repeat(Sequence([User(Leaf("a")), User(Leaf("b"))]), 2)"#);
+        let tick = harness.tick(&MockModel::new().leaf("a", Complete));
+        assert_eq!(tick.state, Waiting);
+        let tick = harness.tick(&MockModel::new().leaf("a", Complete).leaf("b", Failed));
+        assert_eq!(tick.state, Failed);
+        assert_eq!(tick.ran, ["b"]);
+        // A fresh count of two runs.
+        let tick = harness.tick(&MockModel::new().leaf("a", Complete).leaf("b", Complete));
+        assert_eq!(tick.state, Complete);
+        assert_eq!(tick.ran, ["a", "b", "a", "b"]);
+    }
+
+    #[test]
+    fn repeat_until_fail_completes_when_its_node_fails() {
+        let mut harness = TreeHarness::new(r#"RepeatUntilFail(User(Leaf("a")))"#);
+        let states = harness.run(&[MockModel::new(), MockModel::new().leaf("a", Failed)]);
+        assert_eq!(states, [Waiting, Complete]);
+    }
+
+    #[test]
+    fn repeat_until_success_completes_when_its_node_completes() {
+        let mut harness =
+            TreeHarness::new(r#"RepeatUntilSuccess(Sequence([User(Leaf("a")), User(Leaf("b"))]))"#);
+        let tick = harness.tick(&MockModel::new().leaf("a", Failed));
+        assert_eq!(tick.state, Waiting);
+        let tick = harness.tick(&MockModel::new().leaf("a", Complete).leaf("b", Complete));
+        assert_eq!(tick.state, Complete);
+        assert_eq!(tick.ran, ["a", "b"]);
+    }
+
+    #[test]
+    fn repeats_give_up_the_tick_at_the_limit() {
+        for tree in [
+            r#"Repeat(User(Leaf("a")), 10)"#,
+            r#"RepeatUntilFail(User(Leaf("a")))"#,
+        ] {
+            let mut harness = harness_with_limit(tree, 4);
+            let tick = harness.tick(&MockModel::new().leaf("a", Complete));
+            assert_eq!(tick.state, Waiting, "{}", tree);
+            assert_eq!(tick.ran.len(), 4, "{}", tree);
+            assert_eq!(tick.markers(), ["Repeat limit"], "{}", tree);
+        }
+        let mut harness = harness_with_limit(r#"RepeatUntilSuccess(User(Leaf("a")))"#, 4);
+        let tick = harness.tick(&MockModel::new().leaf("a", Failed));
+        assert_eq!(tick.state, Waiting);
+        assert_eq!(tick.ran.len(), 4);
+        assert_eq!(tick.markers(), ["Repeat limit"]);
+    }
+
+    #[test]
+    fn every_hit_of_the_limit_is_marked() {
+        let mut harness = harness_with_limit(r#"RepeatUntilFail(User(Leaf("a")))"#, 4);
+        for _ in 0..3 {
+            let tick = harness.tick(&MockModel::new().leaf("a", Complete));
+            assert_eq!(tick.state, Waiting);
+            assert_eq!(tick.markers(), ["Repeat limit"]);
+        }
+    }
+
+    #[test]
+    fn the_limit_is_logged_once_until_reset() {
+        let mut warned = false;
+        let name = "loop".to_string();
+        assert!(!super::repeat_limit_hit(
+            &name,
+            3,
+            4,
+            &mut warned,
+            &mut None
+        ));
+        assert!(!warned);
+        assert!(super::repeat_limit_hit(&name, 4, 4, &mut warned, &mut None));
+        assert!(warned);
+        assert!(super::repeat_limit_hit(&name, 4, 4, &mut warned, &mut None));
+        assert!(warned);
+    }
 }
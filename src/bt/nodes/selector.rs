@@ -7,9 +7,12 @@ pub struct Selector<M, C> {
 }
 
 impl<M, C> Selector<M, C> {
-    pub fn new(nodes: Vec<Box<dyn BehaviorTree<Model = M, Controller = C> + Send + Sync>>) -> Self {
+    pub fn new(
+        name: String,
+        nodes: Vec<Box<dyn BehaviorTree<Model = M, Controller = C> + Send + Sync>>,
+    ) -> Self {
         Selector {
-            name: get_bt_id(),
+            name,
             nodes,
             index: None,
         }
@@ -23,14 +26,19 @@ impl<M: 'static, C: 'static> BehaviorTree for Selector<M, C> {
         self: &mut Self,
         model: &Self::Model,
         controller: &mut Self::Controller,
+        blackboard: &mut Blackboard,
         gas: &mut Option<i32>,
         mut audit: &mut Option<&mut BehaviorTreeAudit>,
     ) -> BehaviorTreeState {
         audit.enter(self.get_name());
+        if !use_gas(gas) {
+            audit.exit(self.get_name(), BehaviorTreeState::WaitingForGas);
+            return BehaviorTreeState::WaitingForGas;
+        }
         let mut running_index = self.index.unwrap_or(0);
         loop {
             if let Some(node) = self.nodes.get_mut(running_index) {
-                let result = node.resume_with(model, controller, gas, audit);
+                let result = node.resume_with(model, controller, blackboard, gas, audit);
                 match result {
                     BehaviorTreeState::Failed => {
                         // Move on to the next node.
@@ -63,4 +71,79 @@ impl<M: 'static, C: 'static> BehaviorTree for Selector<M, C> {
     fn get_name(self: &Self) -> &String {
         &self.name
     }
+
+    fn get_children(
+        self: &Self,
+    ) -> Vec<&(dyn BehaviorTree<Model = Self::Model, Controller = Self::Controller> + Send + Sync)>
+    {
+        self.nodes.iter().map(|node| &**node).collect()
+    }
+
+    fn get_children_mut(
+        self: &mut Self,
+    ) -> Vec<
+        &mut (dyn BehaviorTree<Model = Self::Model, Controller = Self::Controller> + Send + Sync),
+    > {
+        self.nodes.iter_mut().map(|node| &mut **node as _).collect()
+    }
+
+    fn save_state(self: &Self) -> Option<BehaviorTreeNodeState> {
+        to_node_state(&self.index)
+    }
+
+    fn load_state(self: &mut Self, state: &BehaviorTreeNodeState) -> Result<(), String> {
+        let index = from_node_state(state)?;
+        check_index(index, self.nodes.len())?;
+        self.index = index;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::bt::{testing::*, BehaviorTreeState::*};
+
+    const TREE: &str = r#"Selector([User(Leaf("a")), User(Leaf("b")), User(Leaf("c"))])"#;
+
+    #[test]
+    fn completes_at_the_first_success() {
+        let mut harness = TreeHarness::new(TREE);
+        let tick = harness.tick(&MockModel::new().leaf("a", Failed).leaf("b", Complete));
+        assert_eq!(tick.state, Complete);
+        assert_eq!(tick.ran, ["a", "b"]);
+    }
+
+    #[test]
+    fn fails_once_every_node_fails() {
+        let mut harness = TreeHarness::new(TREE);
+        let tick = harness.tick(
+            &MockModel::new()
+                .leaf("a", Failed)
+                .leaf("b", Failed)
+                .leaf("c", Failed),
+        );
+        assert_eq!(tick.state, Failed);
+        assert_eq!(tick.ran, ["a", "b", "c"]);
+        assert_eq!(
+            tick.exits(),
+            [
+                ("a".to_string(), Failed),
+                ("b".to_string(), Failed),
+                ("c".to_string(), Failed),
+                ("root".to_string(), Failed),
+            ]
+        );
+    }
+
+    #[test]
+    fn resumes_the_waiting_node() {
+        let mut harness = TreeHarness::new(TREE);
+        let tick = harness.tick(&MockModel::new().leaf("a", Failed));
+        assert_eq!(tick.state, Waiting);
+        assert_eq!(tick.ran, ["a", "b"]);
+        // The earlier node isn't checked again while a later one runs.
+        let tick = harness.tick(&MockModel::new().leaf("a", Complete).leaf("b", Failed));
+        assert_eq!(tick.state, Waiting);
+        assert_eq!(tick.ran, ["b", "c"]);
+    }
 }
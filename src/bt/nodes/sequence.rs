@@ -7,9 +7,12 @@ pub struct Sequence<M, C> {
 }
 
 impl<M, C> Sequence<M, C> {
-    pub fn new(nodes: Vec<Box<dyn BehaviorTree<Model = M, Controller = C> + Send + Sync>>) -> Self {
+    pub fn new(
+        name: String,
+        nodes: Vec<Box<dyn BehaviorTree<Model = M, Controller = C> + Send + Sync>>,
+    ) -> Self {
         Sequence {
-            name: get_bt_id(),
+            name,
             nodes,
             index: None,
         }
@@ -23,14 +26,19 @@ impl<M: 'static, C: 'static> BehaviorTree for Sequence<M, C> {
         self: &mut Self,
         model: &Self::Model,
         controller: &mut Self::Controller,
+        blackboard: &mut Blackboard,
         gas: &mut Option<i32>,
         mut audit: &mut Option<&mut BehaviorTreeAudit>,
     ) -> BehaviorTreeState {
         audit.enter(self.get_name());
+        if !use_gas(gas) {
+            audit.exit(self.get_name(), BehaviorTreeState::WaitingForGas);
+            return BehaviorTreeState::WaitingForGas;
+        }
         let mut running_index = self.index.unwrap_or(0);
         loop {
             if let Some(node) = self.nodes.get_mut(running_index) {
-                let result = node.resume_with(model, controller, gas, audit);
+                let result = node.resume_with(model, controller, blackboard, gas, audit);
                 match result {
                     BehaviorTreeState::Complete => {
                         node.reset(model);
@@ -52,6 +60,7 @@ impl<M: 'static, C: 'static> BehaviorTree for Sequence<M, C> {
                 }
             } else {
                 self.index = None;
+                audit.exit(self.get_name(), BehaviorTreeState::Complete);
                 return BehaviorTreeState::Complete;
             }
         }
@@ -64,4 +73,90 @@ impl<M: 'static, C: 'static> BehaviorTree for Sequence<M, C> {
     fn get_name(self: &Self) -> &String {
         &self.name
     }
+
+    fn get_children(
+        self: &Self,
+    ) -> Vec<&(dyn BehaviorTree<Model = Self::Model, Controller = Self::Controller> + Send + Sync)>
+    {
+        self.nodes.iter().map(|node| &**node).collect()
+    }
+
+    fn get_children_mut(
+        self: &mut Self,
+    ) -> Vec<
+        &mut (dyn BehaviorTree<Model = Self::Model, Controller = Self::Controller> + Send + Sync),
+    > {
+        self.nodes.iter_mut().map(|node| &mut **node as _).collect()
+    }
+
+    fn save_state(self: &Self) -> Option<BehaviorTreeNodeState> {
+        to_node_state(&self.index)
+    }
+
+    fn load_state(self: &mut Self, state: &BehaviorTreeNodeState) -> Result<(), String> {
+        let index = from_node_state(state)?;
+        check_index(index, self.nodes.len())?;
+        self.index = index;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::bt::{testing::*, BehaviorTreeState::*};
+
+    const TREE: &str = r#"Sequence([User(Leaf("a")), User(Leaf("b")), User(Leaf("c"))])"#;
+
+    #[test]
+    fn completes_once_every_node_completes() {
+        let mut harness = TreeHarness::new(TREE);
+        let tick = harness.tick(
+            &MockModel::new()
+                .leaf("a", Complete)
+                .leaf("b", Complete)
+                .leaf("c", Complete),
+        );
+        assert_eq!(tick.state, Complete);
+        assert_eq!(tick.ran, ["a", "b", "c"]);
+    }
+
+    #[test]
+    fn fails_at_the_first_failure() {
+        let mut harness = TreeHarness::new(TREE);
+        let tick = harness.tick(&MockModel::new().leaf("a", Complete).leaf("b", Failed));
+        assert_eq!(tick.state, Failed);
+        assert_eq!(tick.ran, ["a", "b"]);
+        assert_eq!(tick.exit_of("root"), Some(Failed));
+    }
+
+    #[test]
+    fn resumes_the_waiting_node() {
+        let mut harness = TreeHarness::new(TREE);
+        let tick = harness.tick(&MockModel::new().leaf("a", Complete));
+        assert_eq!(tick.state, Waiting);
+        assert_eq!(tick.ran, ["a", "b"]);
+        let tick = harness.tick(&MockModel::new().leaf("b", Complete).leaf("c", Complete));
+        assert_eq!(tick.state, Complete);
+        assert_eq!(tick.ran, ["b", "c"]);
+        // Finishing starts it over.
+        let tick = harness.tick(&MockModel::new().leaf("a", Failed));
+        assert_eq!(tick.state, Failed);
+        assert_eq!(tick.ran, ["a"]);
+    }
+
+    #[test]
+    fn picks_up_where_it_ran_out_of_gas() {
+        let mut harness = TreeHarness::new(TREE);
+        harness.gas = Some(3);
+        let model = MockModel::new()
+            .leaf("a", Complete)
+            .leaf("b", Complete)
+            .leaf("c", Complete);
+        let tick = harness.tick(&model);
+        assert_eq!(tick.state, WaitingForGas);
+        assert_eq!(tick.ran, ["a", "b"]);
+        let tick = harness.tick(&model);
+        assert_eq!(tick.state, Complete);
+        assert_eq!(tick.ran, ["c"]);
+    }
 }
@@ -5,11 +5,11 @@ pub struct Succeeder<M, C> {
 }
 
 impl<M, C> Succeeder<M, C> {
-    pub fn new(node: Box<dyn BehaviorTree<Model = M, Controller = C> + Send + Sync>) -> Self {
-        Succeeder {
-            name: get_bt_id(),
-            node,
-        }
+    pub fn new(
+        name: String,
+        node: Box<dyn BehaviorTree<Model = M, Controller = C> + Send + Sync>,
+    ) -> Self {
+        Succeeder { name, node }
     }
 }
 
@@ -20,11 +20,19 @@ impl<M: 'static, C: 'static> BehaviorTree for Succeeder<M, C> {
         self: &mut Self,
         model: &Self::Model,
         controller: &mut Self::Controller,
+        blackboard: &mut Blackboard,
         gas: &mut Option<i32>,
         mut audit: &mut Option<&mut BehaviorTreeAudit>,
     ) -> BehaviorTreeState {
         audit.enter(self.get_name());
-        match self.node.resume_with(model, controller, gas, audit) {
+        if !use_gas(gas) {
+            audit.exit(self.get_name(), BehaviorTreeState::WaitingForGas);
+            return BehaviorTreeState::WaitingForGas;
+        }
+        match self
+            .node
+            .resume_with(model, controller, blackboard, gas, audit)
+        {
             BehaviorTreeState::Failed | BehaviorTreeState::Complete => {
                 audit.exit(self.get_name(), BehaviorTreeState::Complete);
                 return BehaviorTreeState::Complete;
@@ -44,4 +52,43 @@ impl<M: 'static, C: 'static> BehaviorTree for Succeeder<M, C> {
     fn get_name(self: &Self) -> &String {
         &self.name
     }
+
+    fn get_children(
+        self: &Self,
+    ) -> Vec<&(dyn BehaviorTree<Model = Self::Model, Controller = Self::Controller> + Send + Sync)>
+    {
+        vec![&*self.node]
+    }
+
+    fn get_children_mut(
+        self: &mut Self,
+    ) -> Vec<
+        &mut (dyn BehaviorTree<Model = Self::Model, Controller = Self::Controller> + Send + Sync),
+    > {
+        vec![&mut *self.node]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::bt::{testing::*, BehaviorTreeState::*};
+
+    #[test]
+    fn completes_whatever_its_node_finishes_with() {
+        let mut harness = TreeHarness::new(r#"Succeeder(User(Leaf("a")))"#);
+        let states = harness.run(&[
+            MockModel::new().leaf("a", Complete),
+            MockModel::new().leaf("a", Failed),
+            MockModel::new(),
+        ]);
+        assert_eq!(states, [Complete, Complete, Waiting]);
+    }
+
+    #[test]
+    fn audits_its_node_result_and_its_own() {
+        let mut harness = TreeHarness::new(r#"Succeeder(User(Leaf("a")))"#);
+        let tick = harness.tick(&MockModel::new().leaf("a", Failed));
+        assert_eq!(tick.exit_of("a"), Some(Failed));
+        assert_eq!(tick.exit_of("root"), Some(Complete));
+    }
 }
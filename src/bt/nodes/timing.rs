@@ -0,0 +1,441 @@
+use std::collections::VecDeque;
+
+use crate::bt::*;
+
+// Where timing nodes read the time from. Nodes only see the model when they run, so they keep
+// timestamps rather than adding up deltas, which would miss the ticks they sat out.
+pub trait BehaviorTreeClock {
+    // Seconds since some fixed start.
+    fn now(&self) -> f64;
+}
+
+// Fails without running its node until the cooldown has passed since the node last completed.
+// The cooldown outlasts resets, so interrupting the node doesn't skip it.
+pub struct Cooldown<M, C> {
+    name: String,
+    node: Box<dyn BehaviorTree<Model = M, Controller = C> + Send + Sync>,
+    seconds: f32,
+    completed_at: Option<f64>,
+}
+
+impl<M, C> Cooldown<M, C> {
+    pub fn new(
+        name: String,
+        node: Box<dyn BehaviorTree<Model = M, Controller = C> + Send + Sync>,
+        seconds: f32,
+    ) -> Self {
+        Cooldown {
+            name,
+            node,
+            seconds,
+            completed_at: None,
+        }
+    }
+}
+
+impl<M: BehaviorTreeClock + 'static, C: 'static> BehaviorTree for Cooldown<M, C> {
+    type Model = M;
+    type Controller = C;
+    fn resume_with(
+        self: &mut Self,
+        model: &Self::Model,
+        controller: &mut Self::Controller,
+        blackboard: &mut Blackboard,
+        gas: &mut Option<i32>,
+        mut audit: &mut Option<&mut BehaviorTreeAudit>,
+    ) -> BehaviorTreeState {
+        audit.enter(self.get_name());
+        if !use_gas(gas) {
+            audit.exit(self.get_name(), BehaviorTreeState::WaitingForGas);
+            return BehaviorTreeState::WaitingForGas;
+        }
+        if let Some(completed_at) = self.completed_at {
+            if model.now() - completed_at < self.seconds as f64 {
+                audit.mark(&"Cooldown".to_string());
+                audit.exit(self.get_name(), BehaviorTreeState::Failed);
+                return BehaviorTreeState::Failed;
+            }
+        }
+        let result = self
+            .node
+            .resume_with(model, controller, blackboard, gas, audit);
+        if result == BehaviorTreeState::Complete {
+            self.completed_at = Some(model.now());
+        }
+        audit.exit(self.get_name(), result);
+        result
+    }
+
+    fn reset(self: &mut Self, model: &Self::Model) {
+        self.node.reset(model);
+    }
+
+    fn get_name(self: &Self) -> &String {
+        &self.name
+    }
+
+    fn get_children(
+        self: &Self,
+    ) -> Vec<&(dyn BehaviorTree<Model = Self::Model, Controller = Self::Controller> + Send + Sync)>
+    {
+        vec![&*self.node]
+    }
+
+    fn get_children_mut(
+        self: &mut Self,
+    ) -> Vec<
+        &mut (dyn BehaviorTree<Model = Self::Model, Controller = Self::Controller> + Send + Sync),
+    > {
+        vec![&mut *self.node]
+    }
+
+    fn save_state(self: &Self) -> Option<BehaviorTreeNodeState> {
+        to_node_state(&self.completed_at)
+    }
+
+    fn load_state(self: &mut Self, state: &BehaviorTreeNodeState) -> Result<(), String> {
+        self.completed_at = from_node_state(state)?;
+        Ok(())
+    }
+}
+
+// Fails, resetting its node, if the node is still running this long after it started.
+pub struct Timeout<M, C> {
+    name: String,
+    node: Box<dyn BehaviorTree<Model = M, Controller = C> + Send + Sync>,
+    seconds: f32,
+    started_at: Option<f64>,
+}
+
+impl<M, C> Timeout<M, C> {
+    pub fn new(
+        name: String,
+        node: Box<dyn BehaviorTree<Model = M, Controller = C> + Send + Sync>,
+        seconds: f32,
+    ) -> Self {
+        Timeout {
+            name,
+            node,
+            seconds,
+            started_at: None,
+        }
+    }
+}
+
+impl<M: BehaviorTreeClock + 'static, C: 'static> BehaviorTree for Timeout<M, C> {
+    type Model = M;
+    type Controller = C;
+    fn resume_with(
+        self: &mut Self,
+        model: &Self::Model,
+        controller: &mut Self::Controller,
+        blackboard: &mut Blackboard,
+        gas: &mut Option<i32>,
+        mut audit: &mut Option<&mut BehaviorTreeAudit>,
+    ) -> BehaviorTreeState {
+        audit.enter(self.get_name());
+        if !use_gas(gas) {
+            audit.exit(self.get_name(), BehaviorTreeState::WaitingForGas);
+            return BehaviorTreeState::WaitingForGas;
+        }
+        let started_at = *self.started_at.get_or_insert(model.now());
+        if model.now() - started_at >= self.seconds as f64 {
+            audit.mark(&"Timed out".to_string());
+            self.node.reset(model);
+            self.started_at = None;
+            audit.exit(self.get_name(), BehaviorTreeState::Failed);
+            return BehaviorTreeState::Failed;
+        }
+        let result = self
+            .node
+            .resume_with(model, controller, blackboard, gas, audit);
+        if result == BehaviorTreeState::Complete || result == BehaviorTreeState::Failed {
+            self.started_at = None;
+        }
+        audit.exit(self.get_name(), result);
+        result
+    }
+
+    fn reset(self: &mut Self, model: &Self::Model) {
+        self.started_at = None;
+        self.node.reset(model);
+    }
+
+    fn get_name(self: &Self) -> &String {
+        &self.name
+    }
+
+    fn get_children(
+        self: &Self,
+    ) -> Vec<&(dyn BehaviorTree<Model = Self::Model, Controller = Self::Controller> + Send + Sync)>
+    {
+        vec![&*self.node]
+    }
+
+    fn get_children_mut(
+        self: &mut Self,
+    ) -> Vec<
+        &mut (dyn BehaviorTree<Model = Self::Model, Controller = Self::Controller> + Send + Sync),
+    > {
+        vec![&mut *self.node]
+    }
+
+    fn save_state(self: &Self) -> Option<BehaviorTreeNodeState> {
+        to_node_state(&self.started_at)
+    }
+
+    fn load_state(self: &mut Self, state: &BehaviorTreeNodeState) -> Result<(), String> {
+        self.started_at = from_node_state(state)?;
+        Ok(())
+    }
+}
+
+// Waits this long after it is first reached before running its node.
+pub struct Delay<M, C> {
+    name: String,
+    node: Box<dyn BehaviorTree<Model = M, Controller = C> + Send + Sync>,
+    seconds: f32,
+    started_at: Option<f64>,
+}
+
+impl<M, C> Delay<M, C> {
+    pub fn new(
+        name: String,
+        node: Box<dyn BehaviorTree<Model = M, Controller = C> + Send + Sync>,
+        seconds: f32,
+    ) -> Self {
+        Delay {
+            name,
+            node,
+            seconds,
+            started_at: None,
+        }
+    }
+}
+
+impl<M: BehaviorTreeClock + 'static, C: 'static> BehaviorTree for Delay<M, C> {
+    type Model = M;
+    type Controller = C;
+    fn resume_with(
+        self: &mut Self,
+        model: &Self::Model,
+        controller: &mut Self::Controller,
+        blackboard: &mut Blackboard,
+        gas: &mut Option<i32>,
+        mut audit: &mut Option<&mut BehaviorTreeAudit>,
+    ) -> BehaviorTreeState {
+        audit.enter(self.get_name());
+        if !use_gas(gas) {
+            audit.exit(self.get_name(), BehaviorTreeState::WaitingForGas);
+            return BehaviorTreeState::WaitingForGas;
+        }
+        let started_at = *self.started_at.get_or_insert(model.now());
+        if model.now() - started_at < self.seconds as f64 {
+            audit.mark(&"Delayed".to_string());
+            audit.exit(self.get_name(), BehaviorTreeState::Waiting);
+            return BehaviorTreeState::Waiting;
+        }
+        let result = self
+            .node
+            .resume_with(model, controller, blackboard, gas, audit);
+        if result == BehaviorTreeState::Complete || result == BehaviorTreeState::Failed {
+            self.started_at = None;
+        }
+        audit.exit(self.get_name(), result);
+        result
+    }
+
+    fn reset(self: &mut Self, model: &Self::Model) {
+        self.started_at = None;
+        self.node.reset(model);
+    }
+
+    fn get_name(self: &Self) -> &String {
+        &self.name
+    }
+
+    fn get_children(
+        self: &Self,
+    ) -> Vec<&(dyn BehaviorTree<Model = Self::Model, Controller = Self::Controller> + Send + Sync)>
+    {
+        vec![&*self.node]
+    }
+
+    fn get_children_mut(
+        self: &mut Self,
+    ) -> Vec<
+        &mut (dyn BehaviorTree<Model = Self::Model, Controller = Self::Controller> + Send + Sync),
+    > {
+        vec![&mut *self.node]
+    }
+
+    fn save_state(self: &Self) -> Option<BehaviorTreeNodeState> {
+        to_node_state(&self.started_at)
+    }
+
+    fn load_state(self: &mut Self, state: &BehaviorTreeNodeState) -> Result<(), String> {
+        self.started_at = from_node_state(state)?;
+        Ok(())
+    }
+}
+
+// Lets its node start at most `runs` times in any window of this many seconds, and fails
+// without running it otherwise. Like Cooldown, the history outlasts resets.
+pub struct RateLimit<M, C> {
+    name: String,
+    node: Box<dyn BehaviorTree<Model = M, Controller = C> + Send + Sync>,
+    runs: usize,
+    seconds: f32,
+    started_at: VecDeque<f64>,
+    running: bool,
+}
+
+impl<M, C> RateLimit<M, C> {
+    pub fn new(
+        name: String,
+        node: Box<dyn BehaviorTree<Model = M, Controller = C> + Send + Sync>,
+        runs: usize,
+        seconds: f32,
+    ) -> Self {
+        RateLimit {
+            name,
+            node,
+            runs,
+            seconds,
+            started_at: VecDeque::new(),
+            running: false,
+        }
+    }
+}
+
+impl<M: BehaviorTreeClock + 'static, C: 'static> BehaviorTree for RateLimit<M, C> {
+    type Model = M;
+    type Controller = C;
+    fn resume_with(
+        self: &mut Self,
+        model: &Self::Model,
+        controller: &mut Self::Controller,
+        blackboard: &mut Blackboard,
+        gas: &mut Option<i32>,
+        mut audit: &mut Option<&mut BehaviorTreeAudit>,
+    ) -> BehaviorTreeState {
+        audit.enter(self.get_name());
+        if !use_gas(gas) {
+            audit.exit(self.get_name(), BehaviorTreeState::WaitingForGas);
+            return BehaviorTreeState::WaitingForGas;
+        }
+        if !self.running {
+            let now = model.now();
+            while let Some(started_at) = self.started_at.front() {
+                if now - started_at >= self.seconds as f64 {
+                    self.started_at.pop_front();
+                } else {
+                    break;
+                }
+            }
+            if self.started_at.len() >= self.runs {
+                audit.mark(&"Rate limited".to_string());
+                audit.exit(self.get_name(), BehaviorTreeState::Failed);
+                return BehaviorTreeState::Failed;
+            }
+            self.started_at.push_back(now);
+            self.running = true;
+        }
+        let result = self
+            .node
+            .resume_with(model, controller, blackboard, gas, audit);
+        if result == BehaviorTreeState::Complete || result == BehaviorTreeState::Failed {
+            self.running = false;
+        }
+        audit.exit(self.get_name(), result);
+        result
+    }
+
+    fn reset(self: &mut Self, model: &Self::Model) {
+        self.running = false;
+        self.node.reset(model);
+    }
+
+    fn get_name(self: &Self) -> &String {
+        &self.name
+    }
+
+    fn get_children(
+        self: &Self,
+    ) -> Vec<&(dyn BehaviorTree<Model = Self::Model, Controller = Self::Controller> + Send + Sync)>
+    {
+        vec![&*self.node]
+    }
+
+    fn get_children_mut(
+        self: &mut Self,
+    ) -> Vec<
+        &mut (dyn BehaviorTree<Model = Self::Model, Controller = Self::Controller> + Send + Sync),
+    > {
+        vec![&mut *self.node]
+    }
+
+    fn save_state(self: &Self) -> Option<BehaviorTreeNodeState> {
+        to_node_state(&(&self.started_at, self.running))
+    }
+
+    fn load_state(self: &mut Self, state: &BehaviorTreeNodeState) -> Result<(), String> {
+        (self.started_at, self.running) = from_node_state(state)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::bt::{testing::*, BehaviorTreeState::*};
+
+    #[test]
+    fn cooldown_fails_until_the_cooldown_has_passed() {
+        let mut harness = TreeHarness::new(r#"Cooldown(2, User(Leaf("a")))"#);
+        let ticks: Vec<MockTick> = [0., 1., 2.]
+            .iter()
+            .map(|now| harness.tick(&MockModel::new().at(*now).leaf("a", Complete)))
+            .collect();
+        assert_eq!(ticks[0].state, Complete);
+        assert_eq!(ticks[1].state, Failed);
+        assert!(ticks[1].ran.is_empty());
+        assert_eq!(ticks[1].markers(), ["Cooldown"]);
+        assert_eq!(ticks[2].state, Complete);
+    }
+
+    #[test]
+    fn timeout_fails_a_node_that_runs_too_long() {
+        let mut harness = TreeHarness::new(r#"Timeout(2, User(Leaf("a")))"#);
+        let tick = harness.tick(&MockModel::new().at(0.));
+        assert_eq!(tick.state, Waiting);
+        let tick = harness.tick(&MockModel::new().at(2.));
+        assert_eq!(tick.state, Failed);
+        assert!(tick.ran.is_empty());
+        assert_eq!(tick.markers(), ["Timed out"]);
+        // Timing starts over with the next run.
+        assert_eq!(harness.tick(&MockModel::new().at(3.)).state, Waiting);
+    }
+
+    #[test]
+    fn delay_waits_before_starting_its_node() {
+        let mut harness = TreeHarness::new(r#"Delay(1, User(Leaf("a")))"#);
+        let tick = harness.tick(&MockModel::new().at(5.).leaf("a", Complete));
+        assert_eq!(tick.state, Waiting);
+        assert_eq!(tick.markers(), ["Delayed"]);
+        let tick = harness.tick(&MockModel::new().at(6.).leaf("a", Complete));
+        assert_eq!(tick.state, Complete);
+        assert_eq!(tick.ran, ["a"]);
+    }
+
+    #[test]
+    fn rate_limit_fails_once_its_node_started_too_often() {
+        let mut harness = TreeHarness::new(r#"RateLimit(2, 10, User(Leaf("a")))"#);
+        let states = harness.run(&[
+            MockModel::new().at(0.).leaf("a", Complete),
+            MockModel::new().at(1.).leaf("a", Failed),
+            MockModel::new().at(2.).leaf("a", Complete),
+            MockModel::new().at(10.).leaf("a", Complete),
+        ]);
+        assert_eq!(states, [Complete, Failed, Failed, Complete]);
+    }
+}
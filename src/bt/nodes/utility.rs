@@ -0,0 +1,202 @@
+use std::marker::PhantomData;
+
+use serde::{Deserialize, Serialize};
+
+use crate::bt::*;
+
+// How much a model wants a node to run. Only compared against its siblings' scores.
+pub trait BehaviorTreeScorer {
+    type Model;
+
+    fn score(&self, model: &Self::Model) -> f32;
+}
+
+// For trees whose model has no scorers.
+#[derive(Serialize, Deserialize)]
+pub enum NoScorer<M> {
+    #[serde(skip)]
+    Never(PhantomData<fn(&M)>),
+}
+
+impl<M> Clone for NoScorer<M> {
+    fn clone(&self) -> Self {
+        NoScorer::Never(PhantomData)
+    }
+}
+
+impl<M> BehaviorTreeScorer for NoScorer<M> {
+    type Model = M;
+
+    fn score(&self, _model: &Self::Model) -> f32 {
+        0.
+    }
+}
+
+// Runs the highest scoring node, rescoring every tick. A running node keeps going unless
+// another node beats it by more than the hysteresis, and is reset when it is replaced.
+pub struct UtilitySelector<S, M, C> {
+    name: String,
+    nodes: Vec<(
+        S,
+        Box<dyn BehaviorTree<Model = M, Controller = C> + Send + Sync>,
+    )>,
+    hysteresis: f32,
+    index: Option<usize>,
+}
+
+impl<S, M, C> UtilitySelector<S, M, C> {
+    pub fn new(
+        name: String,
+        nodes: Vec<(
+            S,
+            Box<dyn BehaviorTree<Model = M, Controller = C> + Send + Sync>,
+        )>,
+        hysteresis: f32,
+    ) -> Self {
+        UtilitySelector {
+            name,
+            nodes,
+            hysteresis,
+            index: None,
+        }
+    }
+}
+
+impl<S: BehaviorTreeScorer<Model = M>, M: 'static, C: 'static> UtilitySelector<S, M, C> {
+    fn choose(&self, model: &M) -> Option<usize> {
+        let scores: Vec<f32> = self
+            .nodes
+            .iter()
+            .map(|(scorer, _)| scorer.score(model))
+            .collect();
+        let best = (0..scores.len()).fold(None, |best: Option<usize>, index| match best {
+            Some(best) if scores[best] >= scores[index] => Some(best),
+            _ => Some(index),
+        });
+        match (self.index, best) {
+            (Some(running), Some(best)) if scores[best] <= scores[running] + self.hysteresis => {
+                Some(running)
+            }
+            (_, best) => best,
+        }
+    }
+}
+
+impl<S: BehaviorTreeScorer<Model = M>, M: 'static, C: 'static> BehaviorTree
+    for UtilitySelector<S, M, C>
+{
+    type Model = M;
+    type Controller = C;
+    fn resume_with(
+        self: &mut Self,
+        model: &Self::Model,
+        controller: &mut Self::Controller,
+        blackboard: &mut Blackboard,
+        gas: &mut Option<i32>,
+        mut audit: &mut Option<&mut BehaviorTreeAudit>,
+    ) -> BehaviorTreeState {
+        audit.enter(self.get_name());
+        if !use_gas(gas) {
+            audit.exit(self.get_name(), BehaviorTreeState::WaitingForGas);
+            return BehaviorTreeState::WaitingForGas;
+        }
+        let chosen = match self.choose(model) {
+            Some(chosen) => chosen,
+            None => {
+                audit.exit(self.get_name(), BehaviorTreeState::Failed);
+                return BehaviorTreeState::Failed;
+            }
+        };
+        if let Some(running) = self.index {
+            if running != chosen {
+                audit.mark(&format!("{} replaces {}", chosen, running));
+                self.nodes[running].1.reset(model);
+            }
+        }
+        let result = self.nodes[chosen]
+            .1
+            .resume_with(model, controller, blackboard, gas, audit);
+        match result {
+            BehaviorTreeState::Complete | BehaviorTreeState::Failed => {
+                self.nodes[chosen].1.reset(model);
+                self.index = None;
+            }
+            _ => {
+                // Waiting, NeedsGas
+                self.index = Some(chosen);
+            }
+        }
+        audit.exit(self.get_name(), result);
+        result
+    }
+
+    fn reset(self: &mut Self, model: &Self::Model) {
+        if let Some(index) = self.index.take() {
+            self.nodes[index].1.reset(model);
+        }
+    }
+
+    fn get_name(self: &Self) -> &String {
+        &self.name
+    }
+
+    fn get_children(
+        self: &Self,
+    ) -> Vec<&(dyn BehaviorTree<Model = Self::Model, Controller = Self::Controller> + Send + Sync)>
+    {
+        self.nodes.iter().map(|(_, node)| &**node).collect()
+    }
+
+    fn get_children_mut(
+        self: &mut Self,
+    ) -> Vec<
+        &mut (dyn BehaviorTree<Model = Self::Model, Controller = Self::Controller> + Send + Sync),
+    > {
+        self.nodes
+            .iter_mut()
+            .map(|(_, node)| &mut **node as _)
+            .collect()
+    }
+
+    fn save_state(self: &Self) -> Option<BehaviorTreeNodeState> {
+        to_node_state(&self.index)
+    }
+
+    fn load_state(self: &mut Self, state: &BehaviorTreeNodeState) -> Result<(), String> {
+        let index = from_node_state(state)?;
+        check_index(index, self.nodes.len())?;
+        self.index = index;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::bt::{testing::*, BehaviorTreeState::*};
+
+    const TREE: &str = r#"UtilitySelector([
+        (Score("a"), User(Leaf("a"))),
+        (Score("b"), User(Leaf("b"))),
+    ], 0.5)"#;
+
+    #[test]
+    fn runs_the_best_scoring_node() {
+        let mut harness = TreeHarness::new(TREE);
+        let tick = harness.tick(&MockModel::new().score("b", 1.).leaf("b", Complete));
+        assert_eq!(tick.state, Complete);
+        assert_eq!(tick.ran, ["b"]);
+    }
+
+    #[test]
+    fn switches_only_when_beaten_by_more_than_the_hysteresis() {
+        let mut harness = TreeHarness::new(TREE);
+        let tick = harness.tick(&MockModel::new().score("a", 1.));
+        assert_eq!(tick.ran, ["a"]);
+        let tick = harness.tick(&MockModel::new().score("a", 1.).score("b", 1.5));
+        assert_eq!(tick.ran, ["a"]);
+        assert!(tick.markers().is_empty());
+        let tick = harness.tick(&MockModel::new().score("a", 1.).score("b", 1.6));
+        assert_eq!(tick.ran, ["b"]);
+        assert_eq!(tick.markers(), ["1 replaces 0"]);
+    }
+}
@@ -0,0 +1,317 @@
+use std::fmt;
+
+use serde::{
+    de::{self, DeserializeOwned, DeserializeSeed, EnumAccess, VariantAccess, Visitor},
+    Deserialize, Deserializer, Serialize, Serializer,
+};
+
+use super::{
+    BehaviorTree, BehaviorTreeClock, BehaviorTreePredicate, BehaviorTreeScorer, UserNodeDefinition,
+};
+
+type BoxedUserNode<M, C> = Box<dyn RegisteredUserNode<M, C>>;
+
+type DeserializeUserNode<M, C> = for<'de> fn(
+    &mut dyn erased_serde::Deserializer<'de>,
+) -> Result<BoxedUserNode<M, C>, erased_serde::Error>;
+
+// Implemented by a model to say which leaf nodes can appear in its trees.
+pub trait UserNodeRegistration: BehaviorTreeClock + Sized + 'static {
+    type Controller: 'static;
+    type Predicate: BehaviorTreePredicate<Model = Self>
+        + Serialize
+        + DeserializeOwned
+        + Clone
+        + Send
+        + Sync
+        + 'static;
+    type Scorer: BehaviorTreeScorer<Model = Self>
+        + Serialize
+        + DeserializeOwned
+        + Clone
+        + Send
+        + Sync
+        + 'static;
+
+    fn user_nodes() -> &'static UserNodeRegistry<Self, Self::Controller>;
+}
+
+pub trait RegisteredUserNode<M, C>: erased_serde::Serialize + Send + Sync {
+    fn create_node(&self) -> Box<dyn BehaviorTree<Model = M, Controller = C> + Send + Sync>;
+
+    fn clone_node(&self) -> BoxedUserNode<M, C>;
+}
+
+impl<M: 'static, C: 'static, N> RegisteredUserNode<M, C> for N
+where
+    N: BehaviorTree<Model = M, Controller = C> + Serialize + Clone + Send + Sync + 'static,
+{
+    fn create_node(&self) -> Box<dyn BehaviorTree<Model = M, Controller = C> + Send + Sync> {
+        Box::new(self.clone())
+    }
+
+    fn clone_node(&self) -> BoxedUserNode<M, C> {
+        Box::new(self.clone())
+    }
+}
+
+struct Registration<M, C> {
+    name: &'static str,
+    deserialize: DeserializeUserNode<M, C>,
+}
+
+pub struct UserNodeRegistry<M, C> {
+    names: Vec<&'static str>,
+    registrations: Vec<Registration<M, C>>,
+}
+
+impl<M: 'static, C: 'static> Default for UserNodeRegistry<M, C> {
+    fn default() -> Self {
+        UserNodeRegistry {
+            names: Vec::new(),
+            registrations: Vec::new(),
+        }
+    }
+}
+
+impl<M: 'static, C: 'static> UserNodeRegistry<M, C> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn register<N>(mut self, name: &'static str) -> Self
+    where
+        N: BehaviorTree<Model = M, Controller = C>
+            + Serialize
+            + DeserializeOwned
+            + Clone
+            + Send
+            + Sync
+            + 'static,
+    {
+        assert!(
+            !self.names.contains(&name),
+            "user node `{}` registered twice",
+            name
+        );
+        self.names.push(name);
+        self.registrations.push(Registration {
+            name,
+            deserialize: deserialize_user_node::<M, C, N>,
+        });
+        self
+    }
+
+    fn index_of_name(&self, name: &str) -> Option<usize> {
+        self.names.iter().position(|registered| *registered == name)
+    }
+}
+
+fn deserialize_user_node<M: 'static, C: 'static, N>(
+    deserializer: &mut dyn erased_serde::Deserializer<'_>,
+) -> Result<BoxedUserNode<M, C>, erased_serde::Error>
+where
+    N: BehaviorTree<Model = M, Controller = C>
+        + Serialize
+        + DeserializeOwned
+        + Clone
+        + Send
+        + Sync
+        + 'static,
+{
+    let node: N = erased_serde::deserialize(deserializer)?;
+    Ok(Box::new(node))
+}
+
+// A leaf node looked up by name in its model's registry. It is written as an enum variant,
+// so RON reads `User(FireBullet((..)))` and JSON reads `{"User": {"FireBullet": {..}}}`.
+pub struct UserNode<M: UserNodeRegistration> {
+    index: usize,
+    node: BoxedUserNode<M, M::Controller>,
+}
+
+impl<M: UserNodeRegistration> UserNode<M> {
+    pub fn get_type_name(&self) -> &'static str {
+        M::user_nodes().registrations[self.index].name
+    }
+}
+
+impl<M: UserNodeRegistration> Clone for UserNode<M> {
+    fn clone(&self) -> Self {
+        UserNode {
+            index: self.index,
+            node: self.node.clone_node(),
+        }
+    }
+}
+
+impl<M: UserNodeRegistration> UserNodeDefinition for UserNode<M> {
+    type Model = M;
+    type Controller = M::Controller;
+    type Predicate = M::Predicate;
+    type Scorer = M::Scorer;
+
+    fn create_node(
+        &self,
+    ) -> Box<dyn BehaviorTree<Model = Self::Model, Controller = Self::Controller> + Send + Sync>
+    {
+        self.node.create_node()
+    }
+}
+
+impl<M: UserNodeRegistration> Serialize for UserNode<M> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_newtype_variant(
+            "UserNode",
+            self.index as u32,
+            self.get_type_name(),
+            &*self.node as &dyn erased_serde::Serialize,
+        )
+    }
+}
+
+impl<'de, M: UserNodeRegistration> Deserialize<'de> for UserNode<M> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let user_nodes = M::user_nodes();
+        deserializer.deserialize_enum("UserNode", &user_nodes.names, UserNodeVisitor(user_nodes))
+    }
+}
+
+struct UserNodeVisitor<M: 'static, C: 'static>(&'static UserNodeRegistry<M, C>);
+
+impl<'de, M: UserNodeRegistration> Visitor<'de> for UserNodeVisitor<M, M::Controller> {
+    type Value = UserNode<M>;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        write!(formatter, "one of the user nodes {:?}", self.0.names)
+    }
+
+    fn visit_enum<A: EnumAccess<'de>>(self, data: A) -> Result<Self::Value, A::Error> {
+        let (UserNodeName(name), variant) = data.variant()?;
+        let index = self.0.index_of_name(&name).ok_or_else(|| {
+            de::Error::custom(format!(
+                "unknown user node `{}`, expected one of {:?}",
+                name, self.0.names
+            ))
+        })?;
+        let node = variant.newtype_variant_seed(UserNodeSeed(&self.0.registrations[index]))?;
+        Ok(UserNode { index, node })
+    }
+}
+
+struct UserNodeName(String);
+
+impl<'de> Deserialize<'de> for UserNodeName {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer.deserialize_identifier(UserNodeNameVisitor)
+    }
+}
+
+struct UserNodeNameVisitor;
+
+impl<'de> Visitor<'de> for UserNodeNameVisitor {
+    type Value = UserNodeName;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        write!(formatter, "a user node name")
+    }
+
+    fn visit_str<E: de::Error>(self, name: &str) -> Result<Self::Value, E> {
+        Ok(UserNodeName(name.to_string()))
+    }
+}
+
+struct UserNodeSeed<'a, M, C>(&'a Registration<M, C>);
+
+impl<'de, 'a, M, C> DeserializeSeed<'de> for UserNodeSeed<'a, M, C> {
+    type Value = BoxedUserNode<M, C>;
+
+    fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<Self::Value, D::Error> {
+        let mut deserializer = <dyn erased_serde::Deserializer>::erase(deserializer);
+        (self.0.deserialize)(&mut deserializer).map_err(de::Error::custom)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde::de::DeserializeOwned;
+
+    use super::*;
+    use crate::{
+        bt::{testing::MockNode, BehaviorTreeDef, BehaviorTreeLibrary},
+        ten_seconds::{enemies::tree_nodes::EnemyNode, towers::tree_nodes::TowerNode},
+    };
+
+    // Every shipped tree in the folder, with its subtrees filled in from the library.
+    fn shipped_trees<U>(folder: &str, extension: &str, library: &str) -> Vec<BehaviorTreeDef<U>>
+    where
+        U: UserNodeDefinition + Clone,
+        BehaviorTreeDef<U>: DeserializeOwned,
+        BehaviorTreeLibrary<U>: DeserializeOwned,
+    {
+        let read = |path: &std::path::Path| std::fs::read_to_string(path).unwrap();
+        let library: BehaviorTreeLibrary<U> =
+            ron::from_str(&read(&std::path::Path::new(folder).join(library))).unwrap();
+        let mut trees = Vec::new();
+        for entry in std::fs::read_dir(folder).unwrap() {
+            let path = entry.unwrap().path();
+            if path.to_string_lossy().ends_with(extension) {
+                let tree_def: BehaviorTreeDef<U> = ron::from_str(&read(&path))
+                    .unwrap_or_else(|err| panic!("{} doesn't parse: {}", path.display(), err));
+                trees.push(tree_def.expand(&library).unwrap());
+            }
+        }
+        assert!(!trees.is_empty());
+        trees
+    }
+
+    fn assert_round_trips<U: UserNodeDefinition>(tree_def: &BehaviorTreeDef<U>)
+    where
+        BehaviorTreeDef<U>: Serialize + DeserializeOwned,
+    {
+        let ron_text = ron::to_string(tree_def).unwrap();
+        let from_ron: BehaviorTreeDef<U> = ron::from_str(&ron_text).unwrap();
+        assert_eq!(ron::to_string(&from_ron).unwrap(), ron_text);
+        let json_text = serde_json::to_string(tree_def).unwrap();
+        let from_json: BehaviorTreeDef<U> = serde_json::from_str(&json_text).unwrap();
+        assert_eq!(serde_json::to_string(&from_json).unwrap(), json_text);
+        assert_eq!(ron::to_string(&from_json).unwrap(), ron_text);
+    }
+
+    #[test]
+    fn tower_trees_round_trip() {
+        let trees: Vec<BehaviorTreeDef<TowerNode>> = shipped_trees(
+            "assets/trees/towers",
+            ".tower.bt.ron",
+            "library.tower.lib.ron",
+        );
+        for tree_def in trees.iter() {
+            assert_round_trips(tree_def);
+        }
+    }
+
+    #[test]
+    fn enemy_trees_round_trip() {
+        let trees: Vec<BehaviorTreeDef<EnemyNode>> = shipped_trees(
+            "assets/trees/enemies",
+            ".enemy.bt.ron",
+            "library.enemy.lib.ron",
+        );
+        for tree_def in trees.iter() {
+            assert_round_trips(tree_def);
+        }
+    }
+
+    #[test]
+    fn unknown_nodes_are_named() {
+        let expected = "unknown user node `Lef`, expected one of [\"Leaf\"]";
+        let err = ron::from_str::<BehaviorTreeDef<MockNode>>(r#"User(Lef("a"))"#)
+            .err()
+            .unwrap();
+        assert!(err.to_string().contains(expected), "{}", err);
+        let err = serde_json::from_str::<BehaviorTreeDef<MockNode>>(r#"{"User": {"Lef": "a"}}"#)
+            .err()
+            .unwrap();
+        assert!(err.to_string().contains(expected), "{}", err);
+    }
+}
@@ -0,0 +1,293 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    fmt,
+};
+
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+
+use super::BehaviorTree;
+
+// One node's running state, as plain data.
+pub type BehaviorTreeNodeState = serde_json::Value;
+
+// Where a tree instance is in its run, by node id. It holds the tree's progress but not its
+// definition or blackboard, so it can only be restored into a tree built from the same
+// definition.
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+pub struct BehaviorTreeSnapshot {
+    pub nodes: BTreeMap<String, BehaviorTreeNodeState>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum BehaviorTreeRestoreError {
+    // A node that keeps state, with none in the snapshot.
+    MissingNode(String),
+    // State for a node the tree doesn't have.
+    UnknownNode(String),
+    // A node and why its state doesn't fit it.
+    InvalidState(String, String),
+}
+
+impl fmt::Display for BehaviorTreeRestoreError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BehaviorTreeRestoreError::MissingNode(node) => {
+                write!(f, "snapshot has no state for {}", node)
+            }
+            BehaviorTreeRestoreError::UnknownNode(node) => {
+                write!(
+                    f,
+                    "snapshot has state for {}, which is not in the tree",
+                    node
+                )
+            }
+            BehaviorTreeRestoreError::InvalidState(node, err) => {
+                write!(f, "snapshot state doesn't fit {}: {}", node, err)
+            }
+        }
+    }
+}
+
+// For save_state, so a node can hand over any serializable state.
+pub fn to_node_state<T: Serialize>(state: &T) -> Option<BehaviorTreeNodeState> {
+    Some(serde_json::to_value(state).expect("node state should serialize"))
+}
+
+// For load_state, the other way around.
+pub fn from_node_state<T: DeserializeOwned>(state: &BehaviorTreeNodeState) -> Result<T, String> {
+    T::deserialize(state).map_err(|err| err.to_string())
+}
+
+// For composites restoring which of their nodes was running.
+pub fn check_index(index: Option<usize>, nodes: usize) -> Result<(), String> {
+    match index {
+        Some(index) if index >= nodes => Err(format!(
+            "node {} of a composite with {} nodes",
+            index, nodes
+        )),
+        _ => Ok(()),
+    }
+}
+
+pub(super) fn save_states<T: BehaviorTree + ?Sized>(node: &T, snapshot: &mut BehaviorTreeSnapshot) {
+    if let Some(state) = node.save_state() {
+        snapshot.nodes.insert(node.get_name().clone(), state);
+    }
+    for child in node.get_children() {
+        save_states(child, snapshot);
+    }
+}
+
+// Restores what it can, and reports whatever didn't match. Nodes with nothing to restore
+// are left as they were.
+pub(super) fn restore_states<T: BehaviorTree + ?Sized>(
+    node: &mut T,
+    snapshot: &BehaviorTreeSnapshot,
+) -> Result<(), Vec<BehaviorTreeRestoreError>> {
+    let mut errors = Vec::new();
+    let mut restored = Vec::new();
+    restore_at(node, snapshot, &mut restored, &mut errors);
+    errors.extend(
+        snapshot
+            .nodes
+            .keys()
+            .filter(|name| !restored.contains(name))
+            .map(|name| BehaviorTreeRestoreError::UnknownNode(name.clone())),
+    );
+    if errors.is_empty() {
+        Ok(())
+    } else {
+        Err(errors)
+    }
+}
+
+fn restore_at<T: BehaviorTree + ?Sized>(
+    node: &mut T,
+    snapshot: &BehaviorTreeSnapshot,
+    restored: &mut Vec<String>,
+    errors: &mut Vec<BehaviorTreeRestoreError>,
+) {
+    let name = node.get_name().clone();
+    if node.save_state().is_some() {
+        match snapshot.nodes.get(&name) {
+            Some(state) => {
+                if let Err(err) = node.load_state(state) {
+                    errors.push(BehaviorTreeRestoreError::InvalidState(name.clone(), err));
+                }
+                restored.push(name);
+            }
+            None => errors.push(BehaviorTreeRestoreError::MissingNode(name)),
+        }
+    }
+    for child in node.get_children_mut() {
+        restore_at(child, snapshot, restored, errors);
+    }
+}
+
+// Each node's type and running state, by node id.
+fn collect_states<T: BehaviorTree + ?Sized>(
+    node: &T,
+    states: &mut HashMap<String, (&'static str, BehaviorTreeNodeState)>,
+) {
+    if let Some(state) = node.save_state() {
+        states.insert(node.get_name().clone(), (node.get_type_name(), state));
+    }
+    for child in node.get_children() {
+        collect_states(child, states);
+    }
+}
+
+// Returns the nodes that start fresh.
+pub(super) fn migrate_states<T: BehaviorTree + ?Sized>(
+    node: &mut T,
+    old: &(dyn BehaviorTree<Model = T::Model, Controller = T::Controller> + Send + Sync),
+) -> Vec<String> {
+    let mut states = HashMap::new();
+    collect_states(old, &mut states);
+    let mut fresh = Vec::new();
+    migrate_at(node, &states, &mut fresh);
+    fresh
+}
+
+fn migrate_at<T: BehaviorTree + ?Sized>(
+    node: &mut T,
+    states: &HashMap<String, (&'static str, BehaviorTreeNodeState)>,
+    fresh: &mut Vec<String>,
+) {
+    if node.save_state().is_some() {
+        let migrated = match states.get(node.get_name()) {
+            Some((type_name, state)) if *type_name == node.get_type_name() => {
+                node.load_state(state).is_ok()
+            }
+            _ => false,
+        };
+        if !migrated {
+            fresh.push(node.get_name().clone());
+        }
+    }
+    for child in node.get_children_mut() {
+        migrate_at(child, states, fresh);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bt::{testing::*, BehaviorTreeState::*};
+
+    const TREE: &str = r#"Sequence([
+        User(Leaf("a")),
+        Repeat(Sequence([User(Leaf("b")), User(Leaf("c"))]), 3),
+    ])"#;
+
+    fn all_complete() -> MockModel {
+        MockModel::new()
+            .leaf("a", Complete)
+            .leaf("b", Complete)
+            .leaf("c", Complete)
+    }
+
+    #[test]
+    fn restored_tree_picks_up_where_the_snapshot_was() {
+        let mut original = TreeHarness::new(TREE);
+        original.tick(&MockModel::new().leaf("a", Complete).leaf("b", Complete));
+        original.tick(&MockModel::new().leaf("c", Complete));
+        let snapshot = original.tree.snapshot();
+        // Snapshots survive being saved.
+        let snapshot: BehaviorTreeSnapshot =
+            ron::from_str(&ron::to_string(&snapshot).unwrap()).unwrap();
+
+        let mut restored = TreeHarness::new(TREE);
+        restored.tree.restore(&snapshot).unwrap();
+        assert_eq!(restored.tree.snapshot(), original.tree.snapshot());
+        let expected = original.tick(&all_complete());
+        let tick = restored.tick(&all_complete());
+        assert_eq!(tick.state, expected.state);
+        assert_eq!(tick.ran, expected.ran);
+        assert_eq!(tick.ran, ["b", "c", "b", "c"]);
+    }
+
+    #[test]
+    fn restore_reports_nodes_that_dont_match() {
+        let mut original = TreeHarness::new(TREE);
+        original.tick(&MockModel::new().leaf("a", Complete));
+        let mut snapshot = original.tree.snapshot();
+        snapshot
+            .nodes
+            .insert("root/7".to_string(), serde_json::json!(null));
+        snapshot.nodes.remove("root/1");
+        snapshot
+            .nodes
+            .insert("root".to_string(), serde_json::json!(2));
+
+        let mut restored = TreeHarness::new(TREE);
+        let mut errors = restored.tree.restore(&snapshot).unwrap_err();
+        errors.sort_by_key(|error| error.to_string());
+        assert_eq!(
+            errors,
+            [
+                BehaviorTreeRestoreError::MissingNode("root/1".to_string()),
+                BehaviorTreeRestoreError::UnknownNode("root/7".to_string()),
+                BehaviorTreeRestoreError::InvalidState(
+                    "root".to_string(),
+                    "node 2 of a composite with 2 nodes".to_string()
+                ),
+            ]
+        );
+        // What didn't fit was left alone.
+        assert_eq!(restored.tick(&MockModel::new()).ran, ["a"]);
+    }
+
+    #[test]
+    fn random_nodes_draw_the_same_after_a_restore() {
+        let tree = r#"Repeat(RandomSelector([
+            User(Leaf("a")),
+            User(Leaf("b")),
+            User(Leaf("c")),
+            User(Leaf("d")),
+        ]), 100)"#;
+        let failing = MockModel::new()
+            .leaf("a", Failed)
+            .leaf("b", Failed)
+            .leaf("c", Failed)
+            .leaf("d", Complete);
+        let mut original = TreeHarness::new(tree);
+        original.tick(&failing);
+        let mut restored = TreeHarness::new(tree);
+        restored.tree.restore(&original.tree.snapshot()).unwrap();
+        for _ in 0..5 {
+            assert_eq!(restored.tick(&failing).ran, original.tick(&failing).ran);
+        }
+    }
+
+    #[test]
+    fn migrated_tree_carries_on_from_the_old_one() {
+        let mut old =
+            TreeHarness::new(r#"Sequence([User(Leaf("a")), User(Leaf("b")), User(Leaf("c"))])"#);
+        old.tick(&MockModel::new().leaf("a", Complete));
+        let mut new = TreeHarness::new(
+            r#"Sequence([User(Leaf("a")), User(Leaf("b")), User(Leaf("c")), User(Leaf("d"))])"#,
+        );
+        let fresh = new.tree.migrate_from(&*old.tree);
+        assert!(fresh.is_empty());
+        let tick = new.tick(&all_complete().leaf("d", Complete));
+        assert_eq!(tick.ran, ["b", "c", "d"]);
+    }
+
+    #[test]
+    fn migrated_nodes_start_fresh_where_the_shapes_differ() {
+        let mut old = TreeHarness::new(
+            r#"Sequence([User(Leaf("a")), Repeat(User(Leaf("b")), 3), Selector([User(Leaf("c"))])])"#,
+        );
+        old.tick(&MockModel::new().leaf("a", Complete));
+        let mut new = TreeHarness::new(
+            r#"Sequence([User(Leaf("a")), Repeat(User(Leaf("b")), 2), Sequence([User(Leaf("c"))])])"#,
+        );
+        // The old Repeat has three runs left, more than the new one has at all, and the last
+        // node changed type.
+        let fresh = new.tree.migrate_from(&*old.tree);
+        assert_eq!(fresh, ["root/1", "root/2"]);
+        let tick = new.tick(&all_complete());
+        assert_eq!(tick.ran, ["b", "b", "c"]);
+    }
+}
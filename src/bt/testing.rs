@@ -0,0 +1,243 @@
+use std::collections::{HashMap, HashSet};
+
+use serde::{Deserialize, Serialize};
+
+use super::*;
+
+// Builds trees over a mock model and ticks them through scripted model states, so node
+// semantics can be tested without the game.
+
+pub type MockNode = UserNode<MockModel>;
+
+pub type MockTree =
+    Box<dyn BehaviorTree<Model = MockModel, Controller = MockController> + Send + Sync>;
+
+lazy_static::lazy_static! {
+    static ref MOCK_NODES: UserNodeRegistry<MockModel, MockController> =
+        UserNodeRegistry::new().register::<MockLeafNode>("Leaf");
+}
+
+// One tick's worth of the world. Leaves not given a result keep waiting.
+#[derive(Clone, Debug, Default)]
+pub struct MockModel {
+    pub now: f64,
+    pub results: HashMap<String, BehaviorTreeState>,
+    pub facts: HashSet<String>,
+    pub scores: HashMap<String, f32>,
+}
+
+impl MockModel {
+    pub fn new() -> Self {
+        MockModel::default()
+    }
+
+    pub fn at(mut self, now: f64) -> Self {
+        self.now = now;
+        self
+    }
+
+    // What the leaf with this name returns when it runs.
+    pub fn leaf(mut self, name: &str, result: BehaviorTreeState) -> Self {
+        self.results.insert(name.to_string(), result);
+        self
+    }
+
+    pub fn fact(mut self, fact: &str) -> Self {
+        self.facts.insert(fact.to_string());
+        self
+    }
+
+    pub fn score(mut self, name: &str, score: f32) -> Self {
+        self.scores.insert(name.to_string(), score);
+        self
+    }
+}
+
+impl BehaviorTreeClock for MockModel {
+    fn now(&self) -> f64 {
+        self.now
+    }
+}
+
+impl UserNodeRegistration for MockModel {
+    type Controller = MockController;
+    type Predicate = MockPredicate;
+    type Scorer = MockScorer;
+
+    fn user_nodes() -> &'static UserNodeRegistry<Self, Self::Controller> {
+        &MOCK_NODES
+    }
+}
+
+// The names of the leaves that ran, in the order they ran.
+#[derive(Clone, Debug, Default)]
+pub struct MockController {
+    pub ran: Vec<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum MockPredicate {
+    Fact(String),
+}
+
+impl BehaviorTreePredicate for MockPredicate {
+    type Model = MockModel;
+
+    fn check(&self, model: &Self::Model) -> bool {
+        match self {
+            MockPredicate::Fact(fact) => model.facts.contains(fact),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum MockScorer {
+    // The model's score by this name, or 0 without one.
+    Score(String),
+}
+
+impl BehaviorTreeScorer for MockScorer {
+    type Model = MockModel;
+
+    fn score(&self, model: &Self::Model) -> f32 {
+        match self {
+            MockScorer::Score(name) => model.scores.get(name).copied().unwrap_or(0.),
+        }
+    }
+}
+
+// Returns whatever the model says it should, written `User(Leaf("name"))`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct MockLeafNode(pub String);
+
+impl BehaviorTree for MockLeafNode {
+    type Model = MockModel;
+    type Controller = MockController;
+
+    fn get_name(self: &Self) -> &String {
+        &self.0
+    }
+
+    fn reset(self: &mut Self, _model: &Self::Model) {
+        // Nothing to do.
+    }
+
+    fn resume_with(
+        self: &mut Self,
+        model: &Self::Model,
+        controller: &mut Self::Controller,
+        _blackboard: &mut Blackboard,
+        gas: &mut Option<i32>,
+        mut audit: &mut Option<&mut BehaviorTreeAudit>,
+    ) -> BehaviorTreeState {
+        audit.enter(&self.0);
+        if !use_gas(gas) {
+            audit.exit(&self.0, BehaviorTreeState::WaitingForGas);
+            return BehaviorTreeState::WaitingForGas;
+        }
+        controller.ran.push(self.0.clone());
+        let result = model
+            .results
+            .get(&self.0)
+            .copied()
+            .unwrap_or(BehaviorTreeState::Waiting);
+        audit.exit(&self.0, result);
+        result
+    }
+}
+
+// What one tick did.
+#[derive(Debug)]
+pub struct MockTick {
+    pub state: BehaviorTreeState,
+    pub ran: Vec<String>,
+    pub audit: BehaviorTreeAudit,
+}
+
+impl MockTick {
+    // The state each node finished the tick in, in the order they finished.
+    pub fn exits(&self) -> Vec<(String, BehaviorTreeState)> {
+        self.audit
+            .get_events()
+            .iter()
+            .filter_map(|event| match event {
+                BehaviorTreeMarker::Exit(name, state) => Some((name.clone(), *state)),
+                _ => None,
+            })
+            .collect()
+    }
+
+    pub fn exit_of(&self, name: &str) -> Option<BehaviorTreeState> {
+        self.exits()
+            .into_iter()
+            .rev()
+            .find(|(exited, _)| exited == name)
+            .map(|(_, state)| state)
+    }
+
+    pub fn markers(&self) -> Vec<String> {
+        self.audit
+            .get_events()
+            .iter()
+            .filter_map(|event| match event {
+                BehaviorTreeMarker::Marker(marker) => Some(marker.clone()),
+                _ => None,
+            })
+            .collect()
+    }
+}
+
+pub struct TreeHarness {
+    pub tree: MockTree,
+    pub blackboard: Blackboard,
+    // Gas for each tick, or None for as much as it takes.
+    pub gas: Option<i32>,
+}
+
+impl TreeHarness {
+    // Panics with the diagnostics if the tree doesn't build.
+    pub fn new(tree: &str) -> Self {
+        Self::build(tree, "{}", &BehaviorTreeSettings::default())
+            .unwrap_or_else(|diagnostics| panic!("tree doesn't build: {:?}", diagnostics))
+    }
+
+    pub fn build(
+        tree: &str,
+        library: &str,
+        settings: &BehaviorTreeSettings,
+    ) -> Result<Self, Vec<BehaviorTreeDiagnostic>> {
+        let tree_def: BehaviorTreeDef<MockNode> =
+            ron::from_str(tree).unwrap_or_else(|err| panic!("tree doesn't parse: {}", err));
+        let library: BehaviorTreeLibrary<MockNode> =
+            ron::from_str(library).unwrap_or_else(|err| panic!("library doesn't parse: {}", err));
+        Ok(TreeHarness {
+            tree: tree_def.create_tree(&library, settings)?,
+            blackboard: Blackboard::default(),
+            gas: None,
+        })
+    }
+
+    pub fn tick(&mut self, model: &MockModel) -> MockTick {
+        let mut controller = MockController::default();
+        let mut audit = BehaviorTreeAudit::default();
+        let mut gas = self.gas;
+        let state = self.tree.resume_with(
+            model,
+            &mut controller,
+            &mut self.blackboard,
+            &mut gas,
+            &mut Some(&mut audit),
+        );
+        MockTick {
+            state,
+            ran: controller.ran,
+            audit,
+        }
+    }
+
+    // Ticks once per model, returning the tree's state after each.
+    pub fn run(&mut self, models: &[MockModel]) -> Vec<BehaviorTreeState> {
+        models.iter().map(|model| self.tick(model).state).collect()
+    }
+}
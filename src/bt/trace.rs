@@ -0,0 +1,259 @@
+use std::{
+    collections::HashMap,
+    fs::File,
+    io::{BufRead, BufReader, BufWriter, Write},
+    path::Path,
+};
+
+use serde::{Deserialize, Serialize};
+
+use super::{BehaviorTreeAudit, BehaviorTreeAuditTrait, BehaviorTreeMarker};
+
+// One tree's audit for one tick.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct BehaviorTreeTraceEntry {
+    pub frame: u64,
+    pub entity: u64,
+    pub events: Vec<BehaviorTreeMarker>,
+}
+
+impl BehaviorTreeTraceEntry {
+    pub fn new(frame: u64, entity: u64, audit: &BehaviorTreeAudit) -> Self {
+        BehaviorTreeTraceEntry {
+            frame,
+            entity,
+            events: audit.get_events().clone(),
+        }
+    }
+
+    // Replays the recorded events into a fresh audit.
+    pub fn replay(&self) -> BehaviorTreeAudit {
+        let mut audit = BehaviorTreeAudit::default();
+        for event in self.events.iter() {
+            match event {
+                BehaviorTreeMarker::Enter(name) => audit.enter(name),
+                BehaviorTreeMarker::Marker(name) => audit.mark(name),
+                BehaviorTreeMarker::Exit(name, state) => audit.exit(name, *state),
+            }
+        }
+        audit
+    }
+}
+
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub enum TraceFormat {
+    JsonLines,
+    Ron,
+}
+
+impl TraceFormat {
+    // `.ron` files are RON, anything else is JSON Lines.
+    pub fn from_path(path: &Path) -> Self {
+        match path.extension().and_then(|extension| extension.to_str()) {
+            Some("ron") => TraceFormat::Ron,
+            _ => TraceFormat::JsonLines,
+        }
+    }
+}
+
+// Writes one entry per line, in either format.
+pub struct BehaviorTreeTraceWriter {
+    format: TraceFormat,
+    writer: BufWriter<File>,
+}
+
+impl BehaviorTreeTraceWriter {
+    pub fn create(path: &Path) -> anyhow::Result<Self> {
+        Ok(BehaviorTreeTraceWriter {
+            format: TraceFormat::from_path(path),
+            writer: BufWriter::new(File::create(path)?),
+        })
+    }
+
+    pub fn write(&mut self, entry: &BehaviorTreeTraceEntry) -> anyhow::Result<()> {
+        let line = match self.format {
+            TraceFormat::JsonLines => serde_json::to_string(entry)?,
+            TraceFormat::Ron => ron::to_string(entry)?,
+        };
+        writeln!(self.writer, "{}", line)?;
+        Ok(())
+    }
+
+    pub fn flush(&mut self) -> anyhow::Result<()> {
+        self.writer.flush()?;
+        Ok(())
+    }
+}
+
+pub fn load_trace(path: &Path) -> anyhow::Result<Vec<BehaviorTreeTraceEntry>> {
+    let format = TraceFormat::from_path(path);
+    let mut entries = Vec::new();
+    for (line_number, line) in BufReader::new(File::open(path)?).lines().enumerate() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let entry = match format {
+            TraceFormat::JsonLines => serde_json::from_str(&line)
+                .map_err(|err| anyhow::anyhow!("line {}: {}", line_number + 1, err))?,
+            TraceFormat::Ron => ron::from_str(&line)
+                .map_err(|err| anyhow::anyhow!("line {}: {}", line_number + 1, err))?,
+        };
+        entries.push(entry);
+    }
+    Ok(entries)
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum TraceDifference {
+    OnlyBefore {
+        frame: u64,
+        entity: u64,
+    },
+    OnlyAfter {
+        frame: u64,
+        entity: u64,
+    },
+    // The first event that differs within a tick, None where one side ran out of events.
+    Changed {
+        frame: u64,
+        entity: u64,
+        index: usize,
+        before: Option<BehaviorTreeMarker>,
+        after: Option<BehaviorTreeMarker>,
+    },
+}
+
+fn index_by_tick(
+    entries: &[BehaviorTreeTraceEntry],
+) -> HashMap<(u64, u64), &BehaviorTreeTraceEntry> {
+    entries
+        .iter()
+        .map(|entry| ((entry.frame, entry.entity), entry))
+        .collect()
+}
+
+// Lines up ticks by frame and entity, so both traces should come from the same scripted run.
+pub fn diff_traces(
+    before: &[BehaviorTreeTraceEntry],
+    after: &[BehaviorTreeTraceEntry],
+) -> Vec<TraceDifference> {
+    let before_ticks = index_by_tick(before);
+    let after_ticks = index_by_tick(after);
+    let mut differences = Vec::new();
+    for before_entry in before.iter() {
+        let (frame, entity) = (before_entry.frame, before_entry.entity);
+        match after_ticks.get(&(frame, entity)) {
+            Some(after_entry) => {
+                let length = before_entry.events.len().max(after_entry.events.len());
+                let first_change = (0..length).find(|index| {
+                    before_entry.events.get(*index) != after_entry.events.get(*index)
+                });
+                if let Some(index) = first_change {
+                    differences.push(TraceDifference::Changed {
+                        frame,
+                        entity,
+                        index,
+                        before: before_entry.events.get(index).cloned(),
+                        after: after_entry.events.get(index).cloned(),
+                    });
+                }
+            }
+            None => differences.push(TraceDifference::OnlyBefore { frame, entity }),
+        }
+    }
+    for after_entry in after.iter() {
+        let (frame, entity) = (after_entry.frame, after_entry.entity);
+        if !before_ticks.contains_key(&(frame, entity)) {
+            differences.push(TraceDifference::OnlyAfter { frame, entity });
+        }
+    }
+    differences
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bt::{testing::*, BehaviorTreeState::*};
+
+    fn record(model: &MockModel) -> BehaviorTreeTraceEntry {
+        let mut harness = TreeHarness::new(r#"Sequence([User(Leaf("a")), User(Leaf("b"))])"#);
+        let tick = harness.tick(model);
+        BehaviorTreeTraceEntry::new(1, 7, &tick.audit)
+    }
+
+    fn round_trip(
+        file_name: &str,
+        entries: &[BehaviorTreeTraceEntry],
+    ) -> Vec<BehaviorTreeTraceEntry> {
+        let path = std::env::temp_dir().join(format!("{}-{}", std::process::id(), file_name));
+        let mut writer = BehaviorTreeTraceWriter::create(&path).unwrap();
+        for entry in entries.iter() {
+            writer.write(entry).unwrap();
+        }
+        writer.flush().unwrap();
+        let loaded = load_trace(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        loaded
+    }
+
+    #[test]
+    fn traces_load_as_they_were_written() {
+        let entries = [
+            record(&MockModel::new().leaf("a", Complete)),
+            BehaviorTreeTraceEntry {
+                frame: 2,
+                ..record(&MockModel::new().leaf("a", Failed))
+            },
+        ];
+        assert_eq!(round_trip("trace.jsonl", &entries), entries);
+        assert_eq!(round_trip("trace.ron", &entries), entries);
+    }
+
+    #[test]
+    fn replays_the_recorded_audit() {
+        let mut harness = TreeHarness::new(r#"Sequence([User(Leaf("a")), User(Leaf("b"))])"#);
+        let tick = harness.tick(&MockModel::new().leaf("a", Complete));
+        let entry = BehaviorTreeTraceEntry::new(1, 7, &tick.audit);
+        assert_eq!(entry.replay().get_events(), tick.audit.get_events());
+    }
+
+    #[test]
+    fn diffs_line_up_ticks() {
+        let before = [
+            record(&MockModel::new().leaf("a", Complete)),
+            BehaviorTreeTraceEntry {
+                frame: 2,
+                ..record(&MockModel::new())
+            },
+        ];
+        let after = [
+            record(&MockModel::new().leaf("a", Failed)),
+            BehaviorTreeTraceEntry {
+                frame: 3,
+                ..record(&MockModel::new())
+            },
+        ];
+        assert!(diff_traces(&before, &before).is_empty());
+        assert_eq!(
+            diff_traces(&before, &after),
+            [
+                TraceDifference::Changed {
+                    frame: 1,
+                    entity: 7,
+                    index: 2,
+                    before: Some(BehaviorTreeMarker::Exit("a".to_string(), Complete)),
+                    after: Some(BehaviorTreeMarker::Exit("a".to_string(), Failed)),
+                },
+                TraceDifference::OnlyBefore {
+                    frame: 2,
+                    entity: 7
+                },
+                TraceDifference::OnlyAfter {
+                    frame: 3,
+                    entity: 7
+                },
+            ]
+        );
+    }
+}
@@ -1,34 +1,90 @@
-use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 
-use super::{nodes::*, BehaviorTree};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+
+use super::{
+    nodes::*, BehaviorTree, BehaviorTreeDiagnostic, BehaviorTreeLibrary, BlackboardComparison,
+    BlackboardValue, DiagnosticSeverity,
+};
 
 #[derive(Serialize, Deserialize, Clone)]
+#[serde(bound(
+    serialize = "U: Serialize, U::Predicate: Serialize, U::Scorer: Serialize",
+    deserialize = "U: Deserialize<'de>, U::Predicate: Deserialize<'de>, U::Scorer: Deserialize<'de>"
+))]
 pub enum BehaviorTreeDef<U: UserNodeDefinition> {
     Sequence(Vec<BehaviorTreeDef<U>>),
     Selector(Vec<BehaviorTreeDef<U>>),
+    ReactiveSequence(Vec<BehaviorTreeDef<U>>),
+    ReactiveSelector(Vec<BehaviorTreeDef<U>>),
+    Parallel(Vec<BehaviorTreeDef<U>>, ParallelPolicy, ParallelPolicy),
+    // Scored nodes, and how far a node must outscore the running one to replace it.
+    UtilitySelector(Vec<(U::Scorer, BehaviorTreeDef<U>)>, f32),
+    RandomSelector(Vec<BehaviorTreeDef<U>>),
+    // Nodes and their chances of being picked, relative to each other.
+    WeightedRandom(Vec<(f32, BehaviorTreeDef<U>)>),
     Repeat(Box<BehaviorTreeDef<U>>, usize),
     RepeatUntilSuccess(Box<BehaviorTreeDef<U>>),
     RepeatUntilFail(Box<BehaviorTreeDef<U>>),
     Succeeder(Box<BehaviorTreeDef<U>>),
     Failer(Box<BehaviorTreeDef<U>>),
     Inverter(Box<BehaviorTreeDef<U>>),
+    // Seconds after the node completes before it can run again.
+    Cooldown(f32, Box<BehaviorTreeDef<U>>),
+    // Seconds the node may keep running before it fails.
+    Timeout(f32, Box<BehaviorTreeDef<U>>),
+    // Seconds to wait before starting the node.
+    Delay(f32, Box<BehaviorTreeDef<U>>),
+    // How many times the node may start in any window of this many seconds.
+    RateLimit(usize, f32, Box<BehaviorTreeDef<U>>),
+    SetBlackboard(String, BlackboardValue),
+    CompareBlackboard(String, BlackboardComparison, BlackboardValue),
+    ClearBlackboard(String),
+    Condition(U::Predicate),
+    Guard(U::Predicate, Box<BehaviorTreeDef<U>>),
+    // A subtree from the library, and values for its parameters.
+    Ref(String, HashMap<String, String>),
     User(U),
 }
 
+// How trees are built, beyond what their definitions say.
+#[derive(Clone, Debug)]
+pub struct BehaviorTreeSettings {
+    // Random nodes draw from generators seeded from this, so two trees built with the same
+    // seed make the same choices.
+    pub seed: u64,
+    // How many times a repeat node may run its node in one tick before it gives up the tick,
+    // so a node that always finishes straight away can't hang the frame.
+    pub max_repeats_per_tick: usize,
+}
+
+impl Default for BehaviorTreeSettings {
+    fn default() -> Self {
+        BehaviorTreeSettings {
+            seed: 0,
+            max_repeats_per_tick: 1000,
+        }
+    }
+}
+
 pub trait UserNodeDefinition {
-    type Model: 'static;
+    type Model: BehaviorTreeClock + 'static;
     type Controller: 'static;
+    type Predicate: BehaviorTreePredicate<Model = Self::Model> + Clone + Send + Sync + 'static;
+    type Scorer: BehaviorTreeScorer<Model = Self::Model> + Clone + Send + Sync + 'static;
     fn create_node(
         &self,
     ) -> Box<dyn BehaviorTree<Model = Self::Model, Controller = Self::Controller> + Send + Sync>;
 }
 
-impl<M: 'static, C: 'static, D: 'static> UserNodeDefinition for D
+impl<M: BehaviorTreeClock + 'static, C: 'static, D: 'static> UserNodeDefinition for D
 where
     D: BehaviorTree<Model = M, Controller = C> + Clone + Send + Sync,
 {
     type Model = M;
     type Controller = C;
+    type Predicate = NoPredicate<M>;
+    type Scorer = NoScorer<M>;
 
     fn create_node(
         &self,
@@ -38,50 +94,313 @@ where
     }
 }
 
-impl<U: UserNodeDefinition> BehaviorTreeDef<U> {
+impl<U: UserNodeDefinition + Clone> BehaviorTreeDef<U>
+where
+    BehaviorTreeDef<U>: DeserializeOwned,
+{
+    // Expands subtree references and checks the result before building it. Fails with every
+    // error found, or with the first reference that can't be expanded.
     pub fn create_tree(
         &self,
+        library: &BehaviorTreeLibrary<U>,
+        settings: &BehaviorTreeSettings,
+    ) -> Result<
+        Box<dyn BehaviorTree<Model = U::Model, Controller = U::Controller> + Send + Sync>,
+        Vec<BehaviorTreeDiagnostic>,
+    > {
+        let expanded = self
+            .expand(library)
+            .map_err(|diagnostic| vec![diagnostic])?;
+        let errors: Vec<BehaviorTreeDiagnostic> = expanded
+            .validate()
+            .into_iter()
+            .filter(|diagnostic| diagnostic.get_severity() == DiagnosticSeverity::Error)
+            .collect();
+        if !errors.is_empty() {
+            return Err(errors);
+        }
+        Ok(expanded.create_tree_at("root".to_string(), settings))
+    }
+}
+
+impl<U: UserNodeDefinition> BehaviorTreeDef<U> {
+    // In the order they're numbered in node paths.
+    pub fn get_children(&self) -> Vec<&BehaviorTreeDef<U>> {
+        match self {
+            BehaviorTreeDef::Sequence(node_defs)
+            | BehaviorTreeDef::Selector(node_defs)
+            | BehaviorTreeDef::ReactiveSequence(node_defs)
+            | BehaviorTreeDef::ReactiveSelector(node_defs)
+            | BehaviorTreeDef::Parallel(node_defs, _, _)
+            | BehaviorTreeDef::RandomSelector(node_defs) => node_defs.iter().collect(),
+            BehaviorTreeDef::UtilitySelector(scored_node_defs, _) => scored_node_defs
+                .iter()
+                .map(|(_, node_def)| node_def)
+                .collect(),
+            BehaviorTreeDef::WeightedRandom(weighted_node_defs) => weighted_node_defs
+                .iter()
+                .map(|(_, node_def)| node_def)
+                .collect(),
+            BehaviorTreeDef::Repeat(node_def, _)
+            | BehaviorTreeDef::RepeatUntilSuccess(node_def)
+            | BehaviorTreeDef::RepeatUntilFail(node_def)
+            | BehaviorTreeDef::Succeeder(node_def)
+            | BehaviorTreeDef::Failer(node_def)
+            | BehaviorTreeDef::Inverter(node_def)
+            | BehaviorTreeDef::Cooldown(_, node_def)
+            | BehaviorTreeDef::Timeout(_, node_def)
+            | BehaviorTreeDef::Delay(_, node_def)
+            | BehaviorTreeDef::RateLimit(_, _, node_def)
+            | BehaviorTreeDef::Guard(_, node_def) => vec![&**node_def],
+            BehaviorTreeDef::SetBlackboard(..)
+            | BehaviorTreeDef::CompareBlackboard(..)
+            | BehaviorTreeDef::ClearBlackboard(_)
+            | BehaviorTreeDef::Condition(_)
+            | BehaviorTreeDef::Ref(..)
+            | BehaviorTreeDef::User(_) => vec![],
+        }
+    }
+
+    // A copy of this node with each of its children replaced by `f` of that child.
+    pub fn try_map_children<E>(
+        &self,
+        mut f: impl FnMut(&BehaviorTreeDef<U>) -> Result<BehaviorTreeDef<U>, E>,
+    ) -> Result<BehaviorTreeDef<U>, E>
+    where
+        U: Clone,
+    {
+        let mut map_all = |node_defs: &Vec<BehaviorTreeDef<U>>| {
+            node_defs.iter().map(&mut f).collect::<Result<Vec<_>, E>>()
+        };
+        Ok(match self {
+            BehaviorTreeDef::Sequence(node_defs) => BehaviorTreeDef::Sequence(map_all(node_defs)?),
+            BehaviorTreeDef::Selector(node_defs) => BehaviorTreeDef::Selector(map_all(node_defs)?),
+            BehaviorTreeDef::ReactiveSequence(node_defs) => {
+                BehaviorTreeDef::ReactiveSequence(map_all(node_defs)?)
+            }
+            BehaviorTreeDef::ReactiveSelector(node_defs) => {
+                BehaviorTreeDef::ReactiveSelector(map_all(node_defs)?)
+            }
+            BehaviorTreeDef::Parallel(node_defs, success_policy, failure_policy) => {
+                BehaviorTreeDef::Parallel(map_all(node_defs)?, *success_policy, *failure_policy)
+            }
+            BehaviorTreeDef::RandomSelector(node_defs) => {
+                BehaviorTreeDef::RandomSelector(map_all(node_defs)?)
+            }
+            BehaviorTreeDef::UtilitySelector(scored_node_defs, hysteresis) => {
+                BehaviorTreeDef::UtilitySelector(
+                    scored_node_defs
+                        .iter()
+                        .map(|(scorer, node_def)| Ok((scorer.clone(), f(node_def)?)))
+                        .collect::<Result<Vec<_>, E>>()?,
+                    *hysteresis,
+                )
+            }
+            BehaviorTreeDef::WeightedRandom(weighted_node_defs) => BehaviorTreeDef::WeightedRandom(
+                weighted_node_defs
+                    .iter()
+                    .map(|(weight, node_def)| Ok((*weight, f(node_def)?)))
+                    .collect::<Result<Vec<_>, E>>()?,
+            ),
+            BehaviorTreeDef::Repeat(node_def, repeats) => {
+                BehaviorTreeDef::Repeat(Box::new(f(node_def)?), *repeats)
+            }
+            BehaviorTreeDef::RepeatUntilSuccess(node_def) => {
+                BehaviorTreeDef::RepeatUntilSuccess(Box::new(f(node_def)?))
+            }
+            BehaviorTreeDef::RepeatUntilFail(node_def) => {
+                BehaviorTreeDef::RepeatUntilFail(Box::new(f(node_def)?))
+            }
+            BehaviorTreeDef::Succeeder(node_def) => {
+                BehaviorTreeDef::Succeeder(Box::new(f(node_def)?))
+            }
+            BehaviorTreeDef::Failer(node_def) => BehaviorTreeDef::Failer(Box::new(f(node_def)?)),
+            BehaviorTreeDef::Inverter(node_def) => {
+                BehaviorTreeDef::Inverter(Box::new(f(node_def)?))
+            }
+            BehaviorTreeDef::Cooldown(seconds, node_def) => {
+                BehaviorTreeDef::Cooldown(*seconds, Box::new(f(node_def)?))
+            }
+            BehaviorTreeDef::Timeout(seconds, node_def) => {
+                BehaviorTreeDef::Timeout(*seconds, Box::new(f(node_def)?))
+            }
+            BehaviorTreeDef::Delay(seconds, node_def) => {
+                BehaviorTreeDef::Delay(*seconds, Box::new(f(node_def)?))
+            }
+            BehaviorTreeDef::RateLimit(runs, seconds, node_def) => {
+                BehaviorTreeDef::RateLimit(*runs, *seconds, Box::new(f(node_def)?))
+            }
+            BehaviorTreeDef::Guard(predicate, node_def) => {
+                BehaviorTreeDef::Guard(predicate.clone(), Box::new(f(node_def)?))
+            }
+            BehaviorTreeDef::SetBlackboard(..)
+            | BehaviorTreeDef::CompareBlackboard(..)
+            | BehaviorTreeDef::ClearBlackboard(_)
+            | BehaviorTreeDef::Condition(_)
+            | BehaviorTreeDef::Ref(..)
+            | BehaviorTreeDef::User(_) => self.clone(),
+        })
+    }
+
+    // Nodes are named by their path from the root, like `root/1/0`, so every tree built from
+    // the same definition names its nodes the same way. User nodes keep their own names.
+    fn create_tree_at(
+        &self,
+        id: String,
+        settings: &BehaviorTreeSettings,
     ) -> Box<dyn BehaviorTree<Model = U::Model, Controller = U::Controller> + Send + Sync> {
+        let child_id = |index: usize| child_path(&id, index);
         match self {
             BehaviorTreeDef::Sequence(node_defs) => {
                 let nodes = node_defs
                     .iter()
-                    .map(|node_def| node_def.create_tree())
+                    .enumerate()
+                    .map(|(index, node_def)| node_def.create_tree_at(child_id(index), settings))
                     .collect();
-                Box::new(Sequence::new(nodes))
+                Box::new(Sequence::new(id, nodes))
             }
             BehaviorTreeDef::Selector(node_defs) => {
                 let nodes = node_defs
                     .iter()
-                    .map(|node_def| node_def.create_tree())
+                    .enumerate()
+                    .map(|(index, node_def)| node_def.create_tree_at(child_id(index), settings))
+                    .collect();
+                Box::new(Selector::new(id, nodes))
+            }
+            BehaviorTreeDef::ReactiveSequence(node_defs) => {
+                let nodes = node_defs
+                    .iter()
+                    .enumerate()
+                    .map(|(index, node_def)| node_def.create_tree_at(child_id(index), settings))
                     .collect();
-                Box::new(Selector::new(nodes))
+                Box::new(ReactiveSequence::new(id, nodes))
+            }
+            BehaviorTreeDef::ReactiveSelector(node_defs) => {
+                let nodes = node_defs
+                    .iter()
+                    .enumerate()
+                    .map(|(index, node_def)| node_def.create_tree_at(child_id(index), settings))
+                    .collect();
+                Box::new(ReactiveSelector::new(id, nodes))
+            }
+            BehaviorTreeDef::Parallel(node_defs, success_policy, failure_policy) => {
+                let nodes = node_defs
+                    .iter()
+                    .enumerate()
+                    .map(|(index, node_def)| node_def.create_tree_at(child_id(index), settings))
+                    .collect();
+                Box::new(Parallel::new(id, nodes, *success_policy, *failure_policy))
+            }
+            BehaviorTreeDef::UtilitySelector(scored_node_defs, hysteresis) => {
+                let nodes = scored_node_defs
+                    .iter()
+                    .enumerate()
+                    .map(|(index, (scorer, node_def))| {
+                        (
+                            scorer.clone(),
+                            node_def.create_tree_at(child_id(index), settings),
+                        )
+                    })
+                    .collect();
+                Box::new(UtilitySelector::new(id, nodes, *hysteresis))
+            }
+            BehaviorTreeDef::RandomSelector(node_defs) => {
+                let nodes = node_defs
+                    .iter()
+                    .enumerate()
+                    .map(|(index, node_def)| node_def.create_tree_at(child_id(index), settings))
+                    .collect();
+                let seed = node_seed(settings.seed, &id);
+                Box::new(RandomSelector::new(id, nodes, seed))
+            }
+            BehaviorTreeDef::WeightedRandom(weighted_node_defs) => {
+                let nodes = weighted_node_defs
+                    .iter()
+                    .enumerate()
+                    .map(|(index, (weight, node_def))| {
+                        (*weight, node_def.create_tree_at(child_id(index), settings))
+                    })
+                    .collect();
+                let seed = node_seed(settings.seed, &id);
+                Box::new(WeightedRandom::new(id, nodes, seed))
             }
             BehaviorTreeDef::Repeat(node_def, repeats) => {
-                let node = node_def.create_tree();
-                Box::new(Repeat::new(node, *repeats))
+                let node = node_def.create_tree_at(child_id(0), settings);
+                Box::new(Repeat::new(
+                    id,
+                    node,
+                    *repeats,
+                    settings.max_repeats_per_tick,
+                ))
             }
             BehaviorTreeDef::RepeatUntilFail(node_def) => {
-                let node = node_def.create_tree();
-                Box::new(RepeatUntilFail::new(node))
+                let node = node_def.create_tree_at(child_id(0), settings);
+                Box::new(RepeatUntilFail::new(
+                    id,
+                    node,
+                    settings.max_repeats_per_tick,
+                ))
             }
             BehaviorTreeDef::RepeatUntilSuccess(node_def) => {
-                let node = node_def.create_tree();
-                Box::new(RepeatUntilSuccess::new(node))
+                let node = node_def.create_tree_at(child_id(0), settings);
+                Box::new(RepeatUntilSuccess::new(
+                    id,
+                    node,
+                    settings.max_repeats_per_tick,
+                ))
             }
             BehaviorTreeDef::Succeeder(node_def) => {
-                let node = node_def.create_tree();
-                Box::new(Succeeder::new(node))
+                let node = node_def.create_tree_at(child_id(0), settings);
+                Box::new(Succeeder::new(id, node))
             }
             BehaviorTreeDef::Inverter(node_def) => {
-                let node = node_def.create_tree();
-                Box::new(Inverter::new(node))
+                let node = node_def.create_tree_at(child_id(0), settings);
+                Box::new(Inverter::new(id, node))
             }
             BehaviorTreeDef::Failer(node_def) => {
-                let node = node_def.create_tree();
-                Box::new(Failer::new(node))
+                let node = node_def.create_tree_at(child_id(0), settings);
+                Box::new(Failer::new(id, node))
+            }
+            BehaviorTreeDef::Cooldown(seconds, node_def) => {
+                let node = node_def.create_tree_at(child_id(0), settings);
+                Box::new(Cooldown::new(id, node, *seconds))
+            }
+            BehaviorTreeDef::Timeout(seconds, node_def) => {
+                let node = node_def.create_tree_at(child_id(0), settings);
+                Box::new(Timeout::new(id, node, *seconds))
+            }
+            BehaviorTreeDef::Delay(seconds, node_def) => {
+                let node = node_def.create_tree_at(child_id(0), settings);
+                Box::new(Delay::new(id, node, *seconds))
+            }
+            BehaviorTreeDef::RateLimit(runs, seconds, node_def) => {
+                let node = node_def.create_tree_at(child_id(0), settings);
+                Box::new(RateLimit::new(id, node, *runs, *seconds))
+            }
+            BehaviorTreeDef::SetBlackboard(key, value) => {
+                Box::new(SetBlackboard::new(id, key.clone(), value.clone()))
+            }
+            BehaviorTreeDef::CompareBlackboard(key, comparison, value) => Box::new(
+                CompareBlackboard::new(id, key.clone(), *comparison, value.clone()),
+            ),
+            BehaviorTreeDef::ClearBlackboard(key) => {
+                Box::new(ClearBlackboard::new(id, key.clone()))
+            }
+            BehaviorTreeDef::Condition(predicate) => {
+                Box::new(Condition::new(id, predicate.clone()))
+            }
+            BehaviorTreeDef::Guard(predicate, node_def) => {
+                let node = node_def.create_tree_at(child_id(0), settings);
+                Box::new(Guard::new(id, predicate.clone(), node))
+            }
+            BehaviorTreeDef::Ref(..) => {
+                unreachable!("subtree references are expanded before trees are built")
             }
             BehaviorTreeDef::User(node_def) => node_def.create_node(),
         }
     }
 }
+
+pub(super) fn child_path(id: &str, index: usize) -> String {
+    format!("{}/{}", id, index)
+}
@@ -0,0 +1,324 @@
+use std::{collections::HashSet, fmt};
+
+use super::{tree_def::child_path, BehaviorTreeDef, UserNodeDefinition};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum DiagnosticSeverity {
+    Warning,
+    Error,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum BehaviorTreeProblem {
+    // A composite with no nodes, which finishes without doing anything.
+    EmptyComposite,
+    // A Repeat that never runs its node.
+    ZeroRepeats,
+    // A RateLimit that never lets its node start.
+    ZeroRuns,
+    // A repeat-until node over a node that can never wait, so once the node gives the wrong
+    // result it loops within a single tick for as long as the model stays the same.
+    SynchronousLoop,
+    // A WeightedRandom where no node can be picked.
+    NoWeights,
+    // A WeightedRandom node that can never be picked, by its index.
+    UnweightedNode(usize),
+    // A weight that's negative, infinite or NaN.
+    InvalidWeight(f32),
+    NegativeSeconds(f32),
+    // Another node already goes by this name, so audits and the debugger can't tell them apart.
+    DuplicateName(String),
+    UnknownSubtree(String),
+    // A subtree reference that hasn't been expanded, so nothing in it has been checked.
+    UnresolvedRef(String),
+    // A subtree and the parameter it was given no value for.
+    MissingParam(String, String),
+    // A subtree and a value it was given that it has no parameter for.
+    UnknownParam(String, String),
+    // A subtree and why it didn't parse once its parameters were filled in.
+    InvalidSubtree(String, String),
+    // Subtrees that end up containing themselves, outermost first.
+    SubtreeCycle(Vec<String>),
+}
+
+impl BehaviorTreeProblem {
+    pub fn get_severity(&self) -> DiagnosticSeverity {
+        match self {
+            BehaviorTreeProblem::UnweightedNode(_) => DiagnosticSeverity::Warning,
+            _ => DiagnosticSeverity::Error,
+        }
+    }
+}
+
+impl fmt::Display for BehaviorTreeProblem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BehaviorTreeProblem::EmptyComposite => write!(f, "composite has no nodes"),
+            BehaviorTreeProblem::ZeroRepeats => write!(f, "repeats zero times"),
+            BehaviorTreeProblem::ZeroRuns => write!(f, "rate limit allows zero runs"),
+            BehaviorTreeProblem::SynchronousLoop => {
+                write!(f, "repeats a node that never waits, so it may loop forever")
+            }
+            BehaviorTreeProblem::NoWeights => write!(f, "no node has a positive weight"),
+            BehaviorTreeProblem::UnweightedNode(index) => {
+                write!(f, "node {} has no weight and is never picked", index)
+            }
+            BehaviorTreeProblem::InvalidWeight(weight) => write!(f, "{} is not a weight", weight),
+            BehaviorTreeProblem::NegativeSeconds(seconds) => {
+                write!(f, "{} is not a duration", seconds)
+            }
+            BehaviorTreeProblem::DuplicateName(name) => {
+                write!(f, "another node is already named {}", name)
+            }
+            BehaviorTreeProblem::UnknownSubtree(name) => write!(f, "no subtree named {}", name),
+            BehaviorTreeProblem::UnresolvedRef(name) => {
+                write!(f, "subtree {} has not been expanded", name)
+            }
+            BehaviorTreeProblem::MissingParam(name, param) => {
+                write!(f, "subtree {} needs a value for {}", name, param)
+            }
+            BehaviorTreeProblem::UnknownParam(name, param) => {
+                write!(f, "subtree {} has no parameter {}", name, param)
+            }
+            BehaviorTreeProblem::InvalidSubtree(name, err) => {
+                write!(f, "subtree {} is not a valid tree: {}", name, err)
+            }
+            BehaviorTreeProblem::SubtreeCycle(cycle) => {
+                write!(f, "subtree contains itself: {}", cycle.join(" -> "))
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct BehaviorTreeDiagnostic {
+    // The name the node will have in the built tree.
+    pub node: String,
+    pub problem: BehaviorTreeProblem,
+}
+
+impl BehaviorTreeDiagnostic {
+    pub fn get_severity(&self) -> DiagnosticSeverity {
+        self.problem.get_severity()
+    }
+}
+
+impl fmt::Display for BehaviorTreeDiagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{:?} at {}: {}",
+            self.get_severity(),
+            self.node,
+            self.problem
+        )
+    }
+}
+
+impl<U: UserNodeDefinition> BehaviorTreeDef<U> {
+    // Checks a definition for mistakes that would otherwise only show up once the tree runs.
+    pub fn validate(&self) -> Vec<BehaviorTreeDiagnostic> {
+        let mut names = HashSet::new();
+        let mut diagnostics = Vec::new();
+        self.validate_at("root".to_string(), &mut names, &mut diagnostics);
+        diagnostics
+    }
+
+    fn validate_at(
+        &self,
+        id: String,
+        names: &mut HashSet<String>,
+        diagnostics: &mut Vec<BehaviorTreeDiagnostic>,
+    ) {
+        let name = match self {
+            BehaviorTreeDef::User(node_def) => node_def.create_node().get_name().clone(),
+            _ => id.clone(),
+        };
+        let mut problems = Vec::new();
+        if !names.insert(name.clone()) {
+            problems.push(BehaviorTreeProblem::DuplicateName(name.clone()));
+        }
+        match self {
+            BehaviorTreeDef::Sequence(node_defs)
+            | BehaviorTreeDef::Selector(node_defs)
+            | BehaviorTreeDef::ReactiveSequence(node_defs)
+            | BehaviorTreeDef::ReactiveSelector(node_defs)
+            | BehaviorTreeDef::Parallel(node_defs, _, _)
+            | BehaviorTreeDef::RandomSelector(node_defs)
+                if node_defs.is_empty() =>
+            {
+                problems.push(BehaviorTreeProblem::EmptyComposite);
+            }
+            BehaviorTreeDef::UtilitySelector(scored_node_defs, _)
+                if scored_node_defs.is_empty() =>
+            {
+                problems.push(BehaviorTreeProblem::EmptyComposite);
+            }
+            BehaviorTreeDef::WeightedRandom(weighted_node_defs) => {
+                for (weight, _) in weighted_node_defs.iter() {
+                    if !weight.is_finite() || *weight < 0. {
+                        problems.push(BehaviorTreeProblem::InvalidWeight(*weight));
+                    }
+                }
+                if weighted_node_defs.is_empty() {
+                    problems.push(BehaviorTreeProblem::EmptyComposite);
+                } else if !weighted_node_defs
+                    .iter()
+                    .any(|(weight, _)| weight.is_finite() && *weight > 0.)
+                {
+                    problems.push(BehaviorTreeProblem::NoWeights);
+                } else {
+                    for (index, (weight, _)) in weighted_node_defs.iter().enumerate() {
+                        if *weight == 0. {
+                            problems.push(BehaviorTreeProblem::UnweightedNode(index));
+                        }
+                    }
+                }
+            }
+            BehaviorTreeDef::Repeat(_, 0) => {
+                problems.push(BehaviorTreeProblem::ZeroRepeats);
+            }
+            BehaviorTreeDef::RepeatUntilSuccess(node_def)
+            | BehaviorTreeDef::RepeatUntilFail(node_def)
+                if !node_def.can_wait() =>
+            {
+                problems.push(BehaviorTreeProblem::SynchronousLoop);
+            }
+            BehaviorTreeDef::Cooldown(seconds, _)
+            | BehaviorTreeDef::Timeout(seconds, _)
+            | BehaviorTreeDef::Delay(seconds, _)
+                if *seconds < 0. || seconds.is_nan() =>
+            {
+                problems.push(BehaviorTreeProblem::NegativeSeconds(*seconds));
+            }
+            BehaviorTreeDef::RateLimit(runs, seconds, _) => {
+                if *runs == 0 {
+                    problems.push(BehaviorTreeProblem::ZeroRuns);
+                }
+                if *seconds < 0. || seconds.is_nan() {
+                    problems.push(BehaviorTreeProblem::NegativeSeconds(*seconds));
+                }
+            }
+            BehaviorTreeDef::Ref(name, _) => {
+                problems.push(BehaviorTreeProblem::UnresolvedRef(name.clone()));
+            }
+            _ => {}
+        }
+        diagnostics.extend(problems.into_iter().map(|problem| BehaviorTreeDiagnostic {
+            node: name.clone(),
+            problem,
+        }));
+        for (index, node_def) in self.get_children().into_iter().enumerate() {
+            node_def.validate_at(child_path(&id, index), names, diagnostics);
+        }
+    }
+
+    // Whether the node might ever return Waiting. User nodes are assumed to, and so are
+    // subtrees that haven't been expanded yet.
+    fn can_wait(&self) -> bool {
+        match self {
+            BehaviorTreeDef::Delay(seconds, node_def) => *seconds > 0. || node_def.can_wait(),
+            BehaviorTreeDef::User(_) | BehaviorTreeDef::Ref(..) => true,
+            BehaviorTreeDef::SetBlackboard(..)
+            | BehaviorTreeDef::CompareBlackboard(..)
+            | BehaviorTreeDef::ClearBlackboard(_)
+            | BehaviorTreeDef::Condition(_) => false,
+            _ => self
+                .get_children()
+                .into_iter()
+                .any(|node_def| node_def.can_wait()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bt::testing::MockNode;
+
+    fn problems(tree: &str) -> Vec<BehaviorTreeProblem> {
+        let tree_def: BehaviorTreeDef<MockNode> = ron::from_str(tree).unwrap();
+        tree_def
+            .validate()
+            .into_iter()
+            .map(|diagnostic| diagnostic.problem)
+            .collect()
+    }
+
+    #[test]
+    fn composites_need_nodes() {
+        assert_eq!(
+            problems("Sequence([])"),
+            [BehaviorTreeProblem::EmptyComposite]
+        );
+        assert_eq!(
+            problems("UtilitySelector([], 0.0)"),
+            [BehaviorTreeProblem::EmptyComposite]
+        );
+    }
+
+    #[test]
+    fn repeats_need_a_count() {
+        assert_eq!(
+            problems(r#"Repeat(User(Leaf("a")), 0)"#),
+            [BehaviorTreeProblem::ZeroRepeats]
+        );
+        assert!(problems(r#"Repeat(User(Leaf("a")), 1)"#).is_empty());
+    }
+
+    #[test]
+    fn names_must_be_unique() {
+        let tree_def: BehaviorTreeDef<MockNode> =
+            ron::from_str(r#"Sequence([User(Leaf("a")), User(Leaf("b")), User(Leaf("a"))])"#)
+                .unwrap();
+        assert_eq!(
+            tree_def.validate(),
+            [BehaviorTreeDiagnostic {
+                node: "a".to_string(),
+                problem: BehaviorTreeProblem::DuplicateName("a".to_string()),
+            }]
+        );
+    }
+
+    #[test]
+    fn repeating_a_node_that_never_waits_loops() {
+        assert_eq!(
+            problems(r#"RepeatUntilFail(Condition(Fact("a")))"#),
+            [BehaviorTreeProblem::SynchronousLoop]
+        );
+        assert_eq!(
+            problems(r#"RepeatUntilSuccess(Sequence([Condition(Fact("a"))]))"#),
+            [BehaviorTreeProblem::SynchronousLoop]
+        );
+        assert!(problems(r#"RepeatUntilFail(Delay(1.0, Condition(Fact("a"))))"#).is_empty());
+    }
+
+    #[test]
+    fn refs_are_unresolved_but_may_wait() {
+        let tree_def: BehaviorTreeDef<MockNode> =
+            ron::from_str(r#"RepeatUntilFail(Ref("patrol", {}))"#).unwrap();
+        assert_eq!(
+            tree_def.validate(),
+            [BehaviorTreeDiagnostic {
+                node: "root/0".to_string(),
+                problem: BehaviorTreeProblem::UnresolvedRef("patrol".to_string()),
+            }]
+        );
+    }
+
+    #[test]
+    fn weights_must_be_finite_and_not_negative() {
+        assert_eq!(
+            problems(r#"WeightedRandom([(inf, User(Leaf("a"))), (1.0, User(Leaf("b")))])"#),
+            [BehaviorTreeProblem::InvalidWeight(f32::INFINITY)]
+        );
+        assert_eq!(
+            problems(r#"WeightedRandom([(-1.0, User(Leaf("a"))), (1.0, User(Leaf("b")))])"#),
+            [BehaviorTreeProblem::InvalidWeight(-1.)]
+        );
+        assert_eq!(
+            problems(r#"WeightedRandom([(NaN, User(Leaf("a")))])"#).len(),
+            2
+        );
+    }
+}
@@ -0,0 +1,6 @@
+#[macro_use]
+extern crate lazy_static;
+
+pub mod bt;
+pub mod prelude;
+pub mod ten_seconds;
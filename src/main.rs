@@ -1,14 +1,6 @@
 #![windows_subsystem = "windows"]
-use crate::prelude::*;
 use bevy_inspector_egui::{RegisterInspectable, WorldInspectorParams, WorldInspectorPlugin};
-use ten_seconds::TenSecondTowersPlugin;
-
-#[macro_use]
-extern crate lazy_static;
-
-mod bt;
-mod prelude;
-mod ten_seconds;
+use ludum_dare_51::{prelude::*, ten_seconds::TenSecondTowersPlugin};
 
 fn main() {
     App::new()
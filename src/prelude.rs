@@ -96,21 +96,24 @@ pub fn get_tile_from_location(
     }
 }
 
+// Whether every spawner could still reach some goal.
 pub fn can_path_from_spawn_if(
     field: &impl Deref<Target = Field>,
     mut newly_invalid: impl FnMut(FieldLocation) -> bool,
 ) -> bool {
-    let path = astar(
-        &FieldLocation(field.source.0, field.source.1),
-        |n| {
-            let mut neighbors = field.get_pathable_neighbors(n);
-            neighbors.retain(|neighbor| !newly_invalid(neighbor.0));
-            neighbors
-        },
-        |n| field.estimate_distance_to_goal(n),
-        |n| field.is_in_goal(n),
-    );
-    path.is_some()
+    field.get_spawners().iter().all(|spawner| {
+        let path = astar(
+            spawner,
+            |n| {
+                let mut neighbors = field.get_pathable_neighbors(n);
+                neighbors.retain(|neighbor| !newly_invalid(neighbor.0));
+                neighbors
+            },
+            |n| field.estimate_distance_to_goal(n),
+            |n| field.is_in_goal(n),
+        );
+        path.is_some()
+    })
 }
 
 pub fn is_valid_tower_location(
@@ -121,7 +124,7 @@ pub fn is_valid_tower_location(
     let valid_location = if let Ok(field_location_contents) =
         field_location_query.get(*field.get_entity(&location))
     {
-        field_location_contents.is_empty()
+        field_location_contents.is_empty() && field.get_terrain(&location).is_buildable()
     } else {
         false
     };
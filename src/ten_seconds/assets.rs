@@ -1,7 +1,20 @@
-use bevy::asset::LoadState;
+use std::{marker::PhantomData, ops::Deref};
+
+use bevy::{
+    asset::{Asset, AssetLoader, LoadContext, LoadState, LoadedAsset},
+    utils::BoxedFuture,
+};
+use serde::de::DeserializeOwned;
 
 use crate::prelude::*;
 
+use super::{
+    enemies::tree_nodes::EnemyNode,
+    field::map::MapDef,
+    seed::{mix_seed, BehaviorTreeSeed, GameSeed},
+    towers::tree_nodes::TowerNode,
+};
+
 pub struct Sprites {
     pub field: Handle<TextureAtlas>,
     pub field_sprite: Handle<Image>,
@@ -25,15 +38,271 @@ pub struct Sounds {
     stings: Vec<HandleUntyped>,
 }
 
+// Keeps every tree definition under assets/trees loaded, so spawned towers and enemies can
+// build their trees straight away.
+pub struct BehaviorTrees {
+    trees: Vec<HandleUntyped>,
+}
+
+// The library that trees with user nodes U expand their Refs from.
+pub struct BehaviorTreeLibraryHandle<U>(pub Handle<BehaviorTreeLibrary<U>>)
+where
+    BehaviorTreeLibrary<U>: Asset;
+
+pub struct BehaviorTreeLibraryLoader<U> {
+    extensions: &'static [&'static str],
+    user_nodes: PhantomData<fn() -> U>,
+}
+
+impl<U> BehaviorTreeLibraryLoader<U> {
+    pub fn new(extensions: &'static [&'static str]) -> Self {
+        BehaviorTreeLibraryLoader {
+            extensions,
+            user_nodes: PhantomData,
+        }
+    }
+}
+
+impl<U> AssetLoader for BehaviorTreeLibraryLoader<U>
+where
+    U: Send + Sync + 'static,
+    BehaviorTreeLibrary<U>: Asset,
+{
+    fn load<'a>(
+        &'a self,
+        bytes: &'a [u8],
+        load_context: &'a mut LoadContext,
+    ) -> BoxedFuture<'a, anyhow::Result<()>> {
+        Box::pin(async move {
+            // Subtrees are only parsed once their parameters are filled in, as trees are built.
+            let library = ron::de::from_bytes::<BehaviorTreeLibrary<U>>(bytes)?;
+            load_context.set_default_asset(LoadedAsset::new(library));
+            Ok(())
+        })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        self.extensions
+    }
+}
+
+// The map games are played on, picked with MAP (a file name under assets/maps, without the
+// .map.ron) or the default one.
+pub struct MapHandle(pub Handle<MapDef>);
+
+// What MAP falls back to, and what's played without one.
+const DEFAULT_MAP: &str = "default";
+
+fn map_path(map: &str) -> String {
+    format!("maps/{}.map.ron", map)
+}
+
+#[derive(Default)]
+pub struct MapDefLoader;
+
+impl AssetLoader for MapDefLoader {
+    fn load<'a>(
+        &'a self,
+        bytes: &'a [u8],
+        load_context: &'a mut LoadContext,
+    ) -> BoxedFuture<'a, anyhow::Result<()>> {
+        Box::pin(async move {
+            let map = ron::de::from_bytes::<MapDef>(bytes)?;
+            let errors = map.validate();
+            if !errors.is_empty() {
+                return Err(anyhow::anyhow!(
+                    "{}: {}",
+                    load_context.path().display(),
+                    errors.join(", ")
+                ));
+            }
+            load_context.set_default_asset(LoadedAsset::new(map));
+            Ok(())
+        })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["map.ron"]
+    }
+}
+
+pub struct BehaviorTreeDefLoader<U> {
+    extensions: &'static [&'static str],
+    user_nodes: PhantomData<fn() -> U>,
+}
+
+impl<U> BehaviorTreeDefLoader<U> {
+    pub fn new(extensions: &'static [&'static str]) -> Self {
+        BehaviorTreeDefLoader {
+            extensions,
+            user_nodes: PhantomData,
+        }
+    }
+}
+
+impl<U> AssetLoader for BehaviorTreeDefLoader<U>
+where
+    U: UserNodeDefinition + DeserializeOwned + Send + Sync + 'static,
+    U::Predicate: DeserializeOwned,
+    U::Scorer: DeserializeOwned,
+    BehaviorTreeDef<U>: Asset,
+{
+    fn load<'a>(
+        &'a self,
+        bytes: &'a [u8],
+        load_context: &'a mut LoadContext,
+    ) -> BoxedFuture<'a, anyhow::Result<()>> {
+        Box::pin(async move {
+            let tree_def = ron::de::from_bytes::<BehaviorTreeDef<U>>(bytes)?;
+            // Rejected trees leave whatever was loaded before in place. Refs are expanded and
+            // checked against the library when the tree is built.
+            let diagnostics: Vec<BehaviorTreeDiagnostic> = tree_def
+                .validate()
+                .into_iter()
+                .filter(|diagnostic| {
+                    !matches!(diagnostic.problem, BehaviorTreeProblem::UnresolvedRef(_))
+                })
+                .collect();
+            for diagnostic in diagnostics.iter() {
+                warn!("{}: {}", load_context.path().display(), diagnostic);
+            }
+            let errors = diagnostics
+                .iter()
+                .filter(|diagnostic| diagnostic.get_severity() == DiagnosticSeverity::Error)
+                .count();
+            if errors > 0 {
+                return Err(anyhow::anyhow!(
+                    "{} has {} error(s)",
+                    load_context.path().display(),
+                    errors
+                ));
+            }
+            load_context.set_default_asset(LoadedAsset::new(tree_def));
+            Ok(())
+        })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        self.extensions
+    }
+}
+
+// Builds a tree whenever an entity gets a new definition (spawn or upgrade), or the definition
+// file itself or the library changes on disk. A tree replacing another takes over whatever
+// running state still fits it, so an upgrade doesn't interrupt the tower.
+pub fn build_behavior_trees<U, T>(
+    mut commands: Commands,
+    seed: Res<GameSeed>,
+    tree_defs: Res<Assets<BehaviorTreeDef<U>>>,
+    libraries: Res<Assets<BehaviorTreeLibrary<U>>>,
+    library_handle: Res<BehaviorTreeLibraryHandle<U>>,
+    mut ev_library: EventReader<AssetEvent<BehaviorTreeLibrary<U>>>,
+    mut ev_tree_def: EventReader<AssetEvent<BehaviorTreeDef<U>>>,
+    changed_query: Query<
+        (
+            Entity,
+            &Handle<BehaviorTreeDef<U>>,
+            Option<&BehaviorTreeSeed>,
+            Option<&T>,
+        ),
+        Changed<Handle<BehaviorTreeDef<U>>>,
+    >,
+    tree_query: Query<(
+        Entity,
+        &Handle<BehaviorTreeDef<U>>,
+        Option<&BehaviorTreeSeed>,
+        Option<&T>,
+    )>,
+) where
+    U: UserNodeDefinition + Clone + Send + Sync + 'static,
+    BehaviorTreeDef<U>: Asset + DeserializeOwned,
+    BehaviorTreeLibrary<U>: Asset,
+    T: Component
+        + From<Box<dyn BehaviorTree<Model = U::Model, Controller = U::Controller> + Send + Sync>>
+        + Deref<
+            Target = Box<
+                dyn BehaviorTree<Model = U::Model, Controller = U::Controller> + Send + Sync,
+            >,
+        >,
+{
+    let mut rebuilt = Vec::new();
+    for ev in ev_tree_def.iter() {
+        match ev {
+            AssetEvent::Created { handle } | AssetEvent::Modified { handle } => {
+                rebuilt.push(handle.clone());
+            }
+            AssetEvent::Removed { .. } => {}
+        }
+    }
+    // Any tree might use the subtrees that changed.
+    let library_changed = ev_library.iter().any(|ev| match ev {
+        AssetEvent::Created { handle } | AssetEvent::Modified { handle } => {
+            *handle == library_handle.0
+        }
+        AssetEvent::Removed { .. } => false,
+    });
+    let library = match libraries.get(&library_handle.0) {
+        Some(library) => library,
+        None => return,
+    };
+    let mut build = |entity: Entity,
+                     handle: &Handle<BehaviorTreeDef<U>>,
+                     tree_seed: Option<&BehaviorTreeSeed>,
+                     old_tree: Option<&T>| {
+        if let Some(tree_def) = tree_defs.get(handle) {
+            let tree_seed = mix_seed(seed.0, tree_seed.map_or(0, |tree_seed| tree_seed.0));
+            let settings = BehaviorTreeSettings {
+                seed: tree_seed,
+                ..Default::default()
+            };
+            match tree_def.create_tree(library, &settings) {
+                Ok(mut tree) => {
+                    if let Some(old_tree) = old_tree {
+                        let fresh = tree.migrate_from(&***old_tree);
+                        if !fresh.is_empty() {
+                            debug!(
+                                "{:?} starts {} over in its new tree",
+                                entity,
+                                fresh.join(", ")
+                            );
+                        }
+                    }
+                    commands.entity(entity).insert(T::from(tree));
+                }
+                Err(diagnostics) => {
+                    // Whatever tree the entity had before stays in place.
+                    for diagnostic in diagnostics.iter() {
+                        warn!("Could not build a tree for {:?}: {}", entity, diagnostic);
+                    }
+                }
+            }
+        }
+    };
+    for (entity, handle, tree_seed, old_tree) in changed_query.iter() {
+        build(entity, handle, tree_seed, old_tree);
+    }
+    if !rebuilt.is_empty() || library_changed {
+        for (entity, handle, tree_seed, old_tree) in tree_query.iter() {
+            if library_changed || rebuilt.contains(handle) {
+                build(entity, handle, tree_seed, old_tree);
+            }
+        }
+    }
+}
+
 pub fn loading_system(
     mut commands: Commands,
     asset_server: Res<AssetServer>,
     sprites: Option<Res<Sprites>>,
     sounds: Option<Res<Sounds>>,
+    behavior_trees: Option<Res<BehaviorTrees>>,
+    map: Option<Res<MapHandle>>,
     mut app_state: ResMut<State<AppState>>,
     mut texture_atlases: ResMut<Assets<TextureAtlas>>,
 ) {
-    if let (Some(sprites), Some(sounds)) = (sprites, sounds) {
+    if let (Some(sprites), Some(sounds), Some(behavior_trees), Some(map)) =
+        (sprites, sounds, behavior_trees, map)
+    {
         let mut handles = vec![
             sprites.field_sprite.id,
             sprites.enemies_sprite.id,
@@ -47,8 +316,23 @@ pub fn loading_system(
             sounds.goal_hit.id,
             sounds.shoot_small.id,
             sounds.shoot_large.id,
+            map.0.id,
         ];
         handles.extend(sounds.stings.iter().map(|handle| handle.id));
+        handles.extend(behavior_trees.trees.iter().map(|handle| handle.id));
+        if asset_server.get_load_state(&map.0) == LoadState::Failed {
+            // The asset server has already logged why.
+            let default_map = map_path(DEFAULT_MAP);
+            if map.0 == asset_server.get_handle(default_map.as_str()) {
+                panic!("{} failed to load, so there is no map to play", default_map);
+            }
+            error!(
+                "The map failed to load, so {} is played instead",
+                default_map
+            );
+            commands.insert_resource(MapHandle(asset_server.load(&default_map)));
+            return;
+        }
         let load_state = asset_server.get_group_load_state(handles);
         if load_state == LoadState::Loaded {
             app_state.set(AppState::MainMenu).unwrap();
@@ -112,5 +396,19 @@ pub fn loading_system(
             shoot_small,
             shoot_large,
         });
+
+        let trees: Vec<HandleUntyped> = asset_server.load_folder("trees").unwrap();
+        commands.insert_resource(BehaviorTreeLibraryHandle::<TowerNode>(
+            asset_server.load("trees/towers/library.tower.lib.ron"),
+        ));
+        commands.insert_resource(BehaviorTreeLibraryHandle::<EnemyNode>(
+            asset_server.load("trees/enemies/library.enemy.lib.ron"),
+        ));
+
+        commands.insert_resource(BehaviorTrees { trees });
+
+        let map = std::env::var("MAP").unwrap_or_else(|_| DEFAULT_MAP.to_string());
+        info!("map: {}", map);
+        commands.insert_resource(MapHandle(asset_server.load(&map_path(&map))));
     }
 }
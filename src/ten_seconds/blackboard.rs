@@ -0,0 +1,53 @@
+use crate::prelude::*;
+
+#[derive(Component, Default, Deref, DerefMut)]
+pub struct BehaviorTreeBlackboard(pub Blackboard);
+
+impl Inspectable for BehaviorTreeBlackboard {
+    type Attributes = ();
+
+    fn ui(
+        &mut self,
+        ui: &mut egui::Ui,
+        _options: Self::Attributes,
+        _context: &mut bevy_inspector_egui::Context,
+    ) -> bool {
+        let mut values: Vec<_> = self.iter().collect();
+        values.sort_by_key(|(key, _)| *key);
+        ui.vertical(|ui| {
+            if values.is_empty() {
+                ui.label("Empty");
+            }
+            for (key, value) in values {
+                ui.label(format!("{}: {:?}", key, value));
+            }
+        });
+        false
+    }
+}
+
+impl BlackboardType for Entity {
+    fn into_value(self) -> BlackboardValue {
+        BlackboardValue::Id(self.to_bits())
+    }
+
+    fn from_value(value: &BlackboardValue) -> Option<Self> {
+        match value {
+            BlackboardValue::Id(bits) => Some(Entity::from_bits(*bits)),
+            _ => None,
+        }
+    }
+}
+
+impl BlackboardType for Vec2 {
+    fn into_value(self) -> BlackboardValue {
+        BlackboardValue::Vector(self.x, self.y)
+    }
+
+    fn from_value(value: &BlackboardValue) -> Option<Self> {
+        match value {
+            BlackboardValue::Vector(x, y) => Some(Vec2::new(*x, *y)),
+            _ => None,
+        }
+    }
+}
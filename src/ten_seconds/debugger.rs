@@ -0,0 +1,278 @@
+use std::{
+    collections::{HashMap, HashSet, VecDeque},
+    path::Path,
+};
+
+use bevy_egui::{egui, EguiContext};
+
+use crate::prelude::*;
+
+use super::{
+    enemies::ai::EnemyBehaviorTree, field::FieldLocationContents, towers::ai::TowerBehaviorTree,
+};
+
+const MAX_LOG_LINES: usize = 500;
+
+// Only the selected and recorded entities' trees are audited, everything else thinks with
+// `&mut None`.
+#[derive(Default)]
+pub struct BehaviorTreeDebugger {
+    selected: Option<Entity>,
+    active_path: Vec<String>,
+    last_states: HashMap<String, BehaviorTreeState>,
+    log: VecDeque<String>,
+    ticks: u32,
+    recorder: Option<BehaviorTreeTraceWriter>,
+    recording: HashSet<Entity>,
+    frame: u64,
+    // The last saved tree, to rewind it and watch a stretch of its run again.
+    snapshot: Option<(Entity, BehaviorTreeSnapshot)>,
+}
+
+impl BehaviorTreeDebugger {
+    // Recording is opt-in, by pointing BT_TRACE at the file to write.
+    pub fn from_env() -> Self {
+        let recorder = std::env::var("BT_TRACE").ok().and_then(|path| {
+            BehaviorTreeTraceWriter::create(Path::new(&path))
+                .map_err(|err| warn!("Could not record behavior trees to {}: {}", path, err))
+                .ok()
+        });
+        BehaviorTreeDebugger {
+            recorder,
+            ..Default::default()
+        }
+    }
+
+    fn select(&mut self, entity: Option<Entity>) {
+        if self.selected != entity {
+            self.selected = entity;
+            self.active_path.clear();
+            self.last_states.clear();
+            self.log.clear();
+            self.ticks = 0;
+        }
+    }
+
+    pub fn get_audit(&self, entity: Entity) -> Option<BehaviorTreeAudit> {
+        if self.selected == Some(entity) || self.recording.contains(&entity) {
+            Some(BehaviorTreeAudit::default())
+        } else {
+            None
+        }
+    }
+
+    pub fn record(&mut self, entity: Entity, audit: &BehaviorTreeAudit) {
+        if self.recording.contains(&entity) {
+            if let Some(recorder) = &mut self.recorder {
+                let entry = BehaviorTreeTraceEntry::new(self.frame, entity.to_bits(), audit);
+                if let Err(err) = recorder.write(&entry) {
+                    warn!("Could not record behavior tree: {}", err);
+                }
+            }
+        }
+        if self.selected == Some(entity) {
+            self.show(audit);
+        }
+    }
+
+    fn show(&mut self, audit: &BehaviorTreeAudit) {
+        self.ticks += 1;
+        // Replay the tick. The first node to exit waiting is the deepest one still running,
+        // so the place at that moment is the active path.
+        let mut replay = BehaviorTreeAudit::default();
+        let mut active_path = None;
+        for event in audit.get_events() {
+            let depth = replay.get_place().len();
+            match event {
+                BehaviorTreeMarker::Enter(name) => {
+                    self.log(depth, format!("> {}", name));
+                    replay.enter(name);
+                }
+                BehaviorTreeMarker::Marker(name) => {
+                    self.log(depth, format!("* {}", name));
+                    replay.mark(name);
+                }
+                BehaviorTreeMarker::Exit(name, state) => {
+                    if active_path.is_none()
+                        && (*state == BehaviorTreeState::Waiting
+                            || *state == BehaviorTreeState::WaitingForGas)
+                    {
+                        active_path = Some(replay.get_place().clone());
+                    }
+                    replay.exit(name, *state);
+                    self.last_states.insert(name.clone(), *state);
+                    self.log(depth.saturating_sub(1), format!("< {} {:?}", name, state));
+                }
+            }
+        }
+        self.active_path = active_path.unwrap_or_default();
+    }
+
+    fn log(&mut self, depth: usize, line: String) {
+        if self.log.len() >= MAX_LOG_LINES {
+            self.log.pop_front();
+        }
+        self.log
+            .push_back(format!("{:>6} {}{}", self.ticks, "  ".repeat(depth), line));
+    }
+}
+
+pub fn advance_debugger_frame(mut debugger: ResMut<BehaviorTreeDebugger>) {
+    debugger.frame += 1;
+    if let Some(recorder) = &mut debugger.recorder {
+        if let Err(err) = recorder.flush() {
+            warn!("Could not record behavior tree: {}", err);
+        }
+    }
+}
+
+pub fn select_for_debugger(
+    mut debugger: ResMut<BehaviorTreeDebugger>,
+    field: Res<Field>,
+    input: Res<Input<MouseButton>>,
+    windows: Res<Windows>,
+    q_camera: Query<(&Camera, &GlobalTransform)>,
+    enemies_query: Query<(Entity, &Transform), With<EnemyType>>,
+) {
+    if input.just_pressed(MouseButton::Middle) {
+        if let Ok((camera, camera_transform)) = q_camera.get_single() {
+            if let Some(window) = windows.get_primary() {
+                if let Some(position) = window.cursor_position() {
+                    let location = screen_to_world(window, position, camera, camera_transform);
+                    let pick_distance = field.tile_size / 2.;
+                    let enemy = enemies_query
+                        .iter()
+                        .map(|(entity, transform)| {
+                            let distance =
+                                get_location_from_transform(transform).distance(location);
+                            (entity, distance)
+                        })
+                        .filter(|(_, distance)| *distance < pick_distance)
+                        .min_by(|(_, a), (_, b)| a.total_cmp(b))
+                        .map(|(entity, _)| entity);
+                    let tower =
+                        get_tile_from_location(location, &field).and_then(|tile| {
+                            match field.get_contents(&FieldLocation(tile.0, tile.1)) {
+                                FieldLocationContents::Tower(entity, _) => Some(*entity),
+                                _ => None,
+                            }
+                        });
+                    debugger.select(enemy.or(tower));
+                }
+            }
+        }
+    }
+}
+
+pub fn show_debugger(
+    mut egui_context: ResMut<EguiContext>,
+    mut debugger: ResMut<BehaviorTreeDebugger>,
+    mut towers_query: Query<&mut TowerBehaviorTree>,
+    mut enemies_query: Query<&mut EnemyBehaviorTree>,
+) {
+    let selected = match debugger.selected {
+        Some(selected) => selected,
+        None => return,
+    };
+    let mut open = true;
+    egui::Window::new("Behavior Tree")
+        .open(&mut open)
+        .default_width(320.)
+        .show(egui_context.ctx_mut(), |ui| {
+            ui.label(format!("{:?}", selected));
+            if debugger.recorder.is_some() {
+                let mut recording = debugger.recording.contains(&selected);
+                if ui.checkbox(&mut recording, "Record").changed() {
+                    if recording {
+                        debugger.recording.insert(selected);
+                    } else {
+                        debugger.recording.remove(&selected);
+                    }
+                }
+            }
+            if let Ok(mut tree) = towers_query.get_mut(selected) {
+                snapshot_ui(ui, &mut *tree.0, selected, &mut debugger);
+                node_ui(ui, &*tree.0, &debugger);
+            } else if let Ok(mut tree) = enemies_query.get_mut(selected) {
+                snapshot_ui(ui, &mut *tree.0, selected, &mut debugger);
+                node_ui(ui, &*tree.0, &debugger);
+            } else {
+                ui.label("No behavior tree.");
+            }
+            ui.separator();
+            egui::ScrollArea::vertical()
+                .max_height(240.)
+                .stick_to_bottom(true)
+                .show(ui, |ui| {
+                    for line in debugger.log.iter() {
+                        ui.monospace(line);
+                    }
+                });
+        });
+    if !open {
+        debugger.select(None);
+    }
+}
+
+fn snapshot_ui<M: 'static, C: 'static>(
+    ui: &mut egui::Ui,
+    tree: &mut (dyn BehaviorTree<Model = M, Controller = C> + Send + Sync),
+    selected: Entity,
+    debugger: &mut BehaviorTreeDebugger,
+) {
+    ui.horizontal(|ui| {
+        if ui.button("Save state").clicked() {
+            debugger.snapshot = Some((selected, tree.snapshot()));
+        }
+        let snapshot = match &debugger.snapshot {
+            Some((entity, snapshot)) if *entity == selected => Some(snapshot),
+            _ => None,
+        };
+        if ui
+            .add_enabled(snapshot.is_some(), egui::Button::new("Restore state"))
+            .clicked()
+        {
+            if let Some(Err(errors)) = snapshot.map(|snapshot| tree.restore(snapshot)) {
+                for error in errors {
+                    warn!("Could not restore {:?}: {}", selected, error);
+                }
+            }
+        }
+    });
+}
+
+fn node_ui<M: 'static, C: 'static>(
+    ui: &mut egui::Ui,
+    node: &(dyn BehaviorTree<Model = M, Controller = C> + Send + Sync),
+    debugger: &BehaviorTreeDebugger,
+) {
+    let name = node.get_name();
+    let state = debugger.last_states.get(name);
+    let text = match state {
+        Some(state) => format!("{} {}: {:?}", node.get_type_name(), name, state),
+        None => format!("{} {}", node.get_type_name(), name),
+    };
+    let text = egui::RichText::new(text);
+    let text = if debugger.active_path.contains(name) {
+        text.strong().color(egui::Color32::YELLOW)
+    } else {
+        match state {
+            Some(BehaviorTreeState::Complete) => text.color(egui::Color32::GREEN),
+            Some(BehaviorTreeState::Failed) => text.color(egui::Color32::RED),
+            _ => text,
+        }
+    };
+    let children = node.get_children();
+    if children.is_empty() {
+        ui.label(text);
+    } else {
+        egui::CollapsingHeader::new(text)
+            .id_source(name)
+            .default_open(true)
+            .show(ui, |ui| {
+                for child in children {
+                    node_ui(ui, child, debugger);
+                }
+            });
+    }
+}
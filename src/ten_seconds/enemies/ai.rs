@@ -1,9 +1,15 @@
-use bevy::utils::HashMap;
-use pathfinding::prelude::{astar, astar_bag, astar_bag_collect, AstarSolution};
-
 use crate::{
     prelude::*,
-    ten_seconds::{field::FieldLocationContents, towers::TowerCooldowns},
+    ten_seconds::{
+        blackboard::BehaviorTreeBlackboard,
+        debugger::BehaviorTreeDebugger,
+        field::{
+            flow_field::{FlowField, PathCost},
+            FieldLocationContents,
+        },
+        seed::BehaviorTreeSeed,
+        towers::TowerCooldowns,
+    },
 };
 
 #[derive(Component, Debug, Inspectable, Default, Clone)]
@@ -14,13 +20,25 @@ pub struct EnemyImpulses {
 }
 
 pub struct EnemyWorldView {
+    pub now: f64,
     pub field_offset_size: (Vec2, f32),
     pub location: Vec2,
     pub tile: FieldLocation,
     pub my_type: EnemyType,
+    pub health: i32,
+    // In tiles walked to the nearest goal.
     pub distance_from_goal: i32,
-    pub shortest_paths: Option<(Vec<Vec<FieldLocation>>, i32)>,
+    // Every neighbor that starts a cheapest path to a goal, and that path's cost.
+    pub next_steps: Option<(Vec<FieldLocation>, i32)>,
     pub neighbor_towers: Vec<(Entity, TowerType)>,
+    // Fixed for the enemy's life, for choices it should stick with.
+    pub seed: u64,
+}
+
+impl BehaviorTreeClock for EnemyWorldView {
+    fn now(&self) -> f64 {
+        self.now
+    }
 }
 
 #[derive(Component, Deref, DerefMut)]
@@ -28,96 +46,155 @@ pub struct EnemyBehaviorTree(
     pub Box<dyn BehaviorTree<Model = EnemyWorldView, Controller = EnemyImpulses> + Send + Sync>,
 );
 
-type MemoizedPaths = HashMap<FieldLocation, (f64, (Vec<Vec<FieldLocation>>, i32))>;
-#[derive(Default, Deref, DerefMut)]
-pub struct BestPaths(pub MemoizedPaths);
+impl From<Box<dyn BehaviorTree<Model = EnemyWorldView, Controller = EnemyImpulses> + Send + Sync>>
+    for EnemyBehaviorTree
+{
+    fn from(
+        tree: Box<
+            dyn BehaviorTree<Model = EnemyWorldView, Controller = EnemyImpulses> + Send + Sync,
+        >,
+    ) -> Self {
+        EnemyBehaviorTree(tree)
+    }
+}
+
+#[derive(Deref, DerefMut)]
+pub struct BestPaths(pub FlowField);
+
+impl Default for BestPaths {
+    fn default() -> Self {
+        BestPaths(FlowField::new(PathCost::Weighted))
+    }
+}
+
+#[derive(Deref, DerefMut)]
+pub struct BestSeekerPaths(pub FlowField);
+
+impl Default for BestSeekerPaths {
+    fn default() -> Self {
+        BestSeekerPaths(FlowField::new(PathCost::Flat))
+    }
+}
 
-#[derive(Default, Deref, DerefMut)]
-pub struct BestSeekerPaths(pub MemoizedPaths);
+pub const ENEMY_GAS_PER_FRAME: i32 = 1024;
+
+#[derive(Deref, DerefMut)]
+pub struct EnemyThinkBudget(pub GasBudget);
+
+impl Default for EnemyThinkBudget {
+    fn default() -> Self {
+        EnemyThinkBudget(GasBudget::new(ENEMY_GAS_PER_FRAME))
+    }
+}
 
 pub fn think_for_enemies(
     time: Res<Time>,
     field: Res<Field>,
+    mut budget: ResMut<EnemyThinkBudget>,
+    mut debugger: ResMut<BehaviorTreeDebugger>,
     mut best_paths: ResMut<BestPaths>,
     mut best_seeker_paths: ResMut<BestSeekerPaths>,
     mut enemies_query: Query<(
+        Entity,
         &Transform,
         &EnemyType,
+        &Health,
+        &BehaviorTreeSeed,
         &mut EnemyBehaviorTree,
+        &mut BehaviorTreeBlackboard,
         &mut EnemyImpulses,
     )>,
 ) {
     let now = time.seconds_since_startup();
-    for (enemy_transform, enemy_type, mut behavior_tree, mut impulses) in enemies_query.iter_mut() {
+    best_paths.refresh(&field);
+    best_seeker_paths.refresh(&field);
+    let enemies = enemies_query
+        .iter()
+        .map(|(entity, ..)| entity)
+        .collect::<Vec<Entity>>();
+    let mut gas = budget.get_gas();
+    let mut starved = None;
+    for index in budget.get_order(enemies.len()) {
+        let (
+            entity,
+            enemy_transform,
+            enemy_type,
+            health,
+            seed,
+            mut behavior_tree,
+            mut blackboard,
+            mut impulses,
+        ) = match enemies_query.get_mut(enemies[index]) {
+            Ok(enemy) => enemy,
+            Err(_) => continue,
+        };
+        if gas == Some(0) {
+            // Skip the pathfinding too, and keep heading the same way until there's gas.
+            starved.get_or_insert(index);
+            continue;
+        }
         let location = Vec2::new(enemy_transform.translation.x, enemy_transform.translation.y);
         if let Some(tile) = get_tile_from_location(location, &field) {
             let tile = FieldLocation(tile.0, tile.1);
-            let shortest_paths =
-                if *enemy_type == EnemyType::Seeker || *enemy_type == EnemyType::Thief {
-                    get_shortest_path(
-                        tile,
-                        &field,
-                        |n| field.get_pathable_neighbors_flat_cost(n),
-                        now,
-                        &mut best_seeker_paths,
-                    )
-                } else {
-                    get_shortest_path(
-                        tile,
-                        &field,
-                        |n| field.get_pathable_neighbors(n),
-                        now,
-                        &mut best_paths,
-                    )
-                };
+            let next_steps = if *enemy_type == EnemyType::Seeker || *enemy_type == EnemyType::Thief
+            {
+                best_seeker_paths.get_next_steps(&field, &tile)
+            } else {
+                best_paths.get_next_steps(&field, &tile)
+            };
             let neighbor_towers = get_neighbor_towers(&field, tile);
             let view = EnemyWorldView {
+                now,
                 field_offset_size: (field.offset, field.tile_size),
-                distance_from_goal: field.estimate_distance_to_goal(&tile),
+                distance_from_goal: best_seeker_paths
+                    .get_distance(&field, &tile)
+                    .unwrap_or(i32::MAX),
                 my_type: *enemy_type,
+                health: health.health,
                 neighbor_towers,
-                shortest_paths,
+                next_steps,
                 location,
                 tile,
+                seed: seed.0,
             };
             let mut new_impulses: EnemyImpulses = Default::default();
-            behavior_tree.resume_with(&view, &mut new_impulses, &mut None, &mut None);
-            *impulses = new_impulses;
-        }
-    }
-}
-
-fn get_shortest_path(
-    tile: FieldLocation,
-    field: &Res<Field>,
-    get_neighbors: impl FnMut(&FieldLocation) -> Vec<(FieldLocation, i32)>,
-    now: f64,
-    memorized: &mut MemoizedPaths,
-) -> Option<(Vec<Vec<FieldLocation>>, i32)> {
-    if let Some((last_calculated, paths)) = memorized.get(&tile) {
-        if now - last_calculated < 1. {
-            return Some(paths.clone());
+            // Names the enemy in anything the tree logs.
+            let _span = info_span!("think", ?entity).entered();
+            let mut audit = debugger.get_audit(entity);
+            let state = behavior_tree.resume_with(
+                &view,
+                &mut new_impulses,
+                &mut blackboard,
+                &mut gas,
+                &mut audit.as_mut(),
+            );
+            if let Some(audit) = &audit {
+                debugger.record(entity, audit);
+            }
+            if state == BehaviorTreeState::WaitingForGas {
+                starved.get_or_insert(index);
+            } else {
+                *impulses = new_impulses;
+            }
         }
     }
-    let shortest = astar_bag_collect(
-        &tile,
-        get_neighbors,
-        |n| field.estimate_distance_to_goal(n),
-        |n| field.is_in_goal(n),
-    );
-    if let Some(paths) = &shortest {
-        memorized.insert(tile, (now, paths.clone()));
-    }
-    shortest
+    budget.set_starved(starved);
 }
 
 pub fn move_enemies(
     time: Res<Time>,
+    field: Res<Field>,
     mut enemies_query: Query<(&mut Transform, &EnemyType, &EnemyImpulses)>,
 ) {
     for (mut transform, enemy_type, impulse) in enemies_query.iter_mut() {
         if let Some(movement) = impulse.move_towards {
-            let delta = time.delta_seconds() * enemy_type.get_speed();
+            // By whatever the enemy is standing on.
+            let speed_multiplier = get_tile_from_transform(&transform, &field).map_or(1., |tile| {
+                field
+                    .get_terrain(&FieldLocation(tile.0, tile.1))
+                    .get_speed_multiplier()
+            });
+            let delta = time.delta_seconds() * enemy_type.get_speed() * speed_multiplier;
             transform.translation += Vec3::new(movement.x * delta, movement.y * delta, 0.);
             transform.rotation = get_rotation_towards(movement);
         }
@@ -125,11 +202,12 @@ pub fn move_enemies(
 }
 
 pub fn steal_ammo(
-    mut enemies_query: Query<(&EnemyType, &EnemyImpulses, &mut Health)>,
+    mut enemies_query: Query<(&EnemyType, &mut EnemyImpulses, &mut Health)>,
     mut ammo_query: Query<&mut TowerCooldowns>,
 ) {
-    for (enemy_type, impulse, mut health) in enemies_query.iter_mut() {
-        if let Some(tower_entity) = impulse.attack_tower {
+    for (enemy_type, mut impulse, mut health) in enemies_query.iter_mut() {
+        // Taken, so a starved thief doesn't keep stealing from a tower it has left.
+        if let Some(tower_entity) = impulse.attack_tower.take() {
             if let Ok(mut tower_cooldowns) = ammo_query.get_mut(tower_entity) {
                 if *enemy_type == EnemyType::Thief {
                     if health.health < health.max_health {
@@ -142,3 +220,93 @@ pub fn steal_ammo(
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ten_seconds::{
+        enemies::tree_nodes::EnemyNode,
+        field::{map::MapDef, Pathability},
+    };
+
+    fn test_field() -> Field {
+        let map: MapDef = ron::from_str(
+            r#"(
+                width: 2,
+                height: 1,
+                tile_size: 32.0,
+                offset: (0.0, 0.0),
+                spawners: [(0, 0)],
+                goals: [(1, 0)],
+                terrain: [],
+                costs: [],
+            )"#,
+        )
+        .unwrap();
+        let field_locations = map
+            .terrains()
+            .into_iter()
+            .map(|_| {
+                (
+                    Entity::from_raw(0),
+                    FieldLocationContents::None,
+                    Pathability::Pathable,
+                )
+            })
+            .collect();
+        Field::new(&map, field_locations)
+    }
+
+    #[test]
+    fn starved_thieves_steal_once() {
+        let mut world = World::new();
+        world.insert_resource(Time::default());
+        world.insert_resource(test_field());
+        world.insert_resource(EnemyThinkBudget(GasBudget::new(0)));
+        world.insert_resource(BehaviorTreeDebugger::default());
+        world.init_resource::<BestPaths>();
+        world.init_resource::<BestSeekerPaths>();
+        let tower = world
+            .spawn()
+            .insert(TowerCooldowns {
+                time_since_shot: 0.,
+                time_since_hit: 0.,
+                ammo_left: 5,
+                max_ammo: 5,
+            })
+            .id();
+        let tree_def: BehaviorTreeDef<EnemyNode> =
+            ron::from_str(r#"ClearBlackboard("a")"#).unwrap();
+        let tree = tree_def
+            .create_tree(&Default::default(), &Default::default())
+            .unwrap_or_else(|_| panic!("tree doesn't build"));
+        let thief = world
+            .spawn()
+            .insert_bundle((
+                Transform::default(),
+                EnemyType::Thief,
+                Health {
+                    max_health: 5,
+                    health: 1,
+                    dead: false,
+                },
+                BehaviorTreeSeed(0),
+                EnemyBehaviorTree(tree),
+                BehaviorTreeBlackboard::default(),
+                // What it last decided, before it ran out of gas.
+                EnemyImpulses {
+                    attack_tower: Some(tower),
+                    ..Default::default()
+                },
+            ))
+            .id();
+        let mut think = SystemStage::single(think_for_enemies);
+        let mut steal = SystemStage::single(steal_ammo);
+        for _ in 0..3 {
+            think.run(&mut world);
+            steal.run(&mut world);
+        }
+        assert_eq!(world.get::<TowerCooldowns>(tower).unwrap().ammo_left, 4);
+        assert_eq!(world.get::<Health>(thief).unwrap().health, 2);
+    }
+}
@@ -1,9 +1,8 @@
 use crate::prelude::*;
 
-use self::{
-    ai::{EnemyBehaviorTree, EnemyImpulses},
-    tree_nodes::{AttackNode, EnemyNode, PathfindNode},
-};
+use self::{ai::EnemyImpulses, tree_nodes::EnemyNode};
+
+use super::{blackboard::BehaviorTreeBlackboard, seed::BehaviorTreeSeed};
 
 pub mod ai;
 pub mod damaged;
@@ -22,28 +21,13 @@ pub enum EnemyType {
 }
 
 impl EnemyType {
-    fn get_behavior_tree(&self) -> EnemyBehaviorTree {
-        let tree_def = match self {
+    fn get_behavior_tree_path(&self) -> &'static str {
+        match self {
             Self::Basic | Self::Fast | Self::Seeker | Self::Buster | Self::Gnat => {
-                BehaviorTreeDef::Sequence(vec![BehaviorTreeDef::User(PathfindNode {
-                    name: "BasicPath".to_string(),
-                    idx: rand::random::<usize>(),
-                })])
-                .create_tree()
+                "trees/enemies/path.enemy.bt.ron"
             }
-            Self::Thief | Self::Mugger => BehaviorTreeDef::Sequence(vec![
-                BehaviorTreeDef::User(EnemyNode::Pathfind(PathfindNode {
-                    name: "BasicPath".to_string(),
-                    idx: rand::random::<usize>(),
-                })),
-                BehaviorTreeDef::User(EnemyNode::Attack(AttackNode {
-                    name: "BasicPath".to_string(),
-                    idx: 0,
-                })),
-            ])
-            .create_tree(),
-        };
-        EnemyBehaviorTree(tree_def)
+            Self::Thief | Self::Mugger => "trees/enemies/thief.enemy.bt.ron",
+        }
     }
 
     fn get_health(&self, boosts: i32) -> Health {
@@ -115,17 +99,26 @@ impl EnemyType {
 #[derive(Bundle)]
 struct EnemyBundle {
     enemy_type: EnemyType,
-    enemy_behavior_tree: EnemyBehaviorTree,
+    enemy_behavior_tree_def: Handle<BehaviorTreeDef<EnemyNode>>,
+    enemy_blackboard: BehaviorTreeBlackboard,
+    enemy_tree_seed: BehaviorTreeSeed,
     enemy_impulses: EnemyImpulses,
     health: Health,
 }
 
 impl EnemyBundle {
-    pub fn new(enemy_type: EnemyType, boosts: i32) -> Self {
+    pub fn new(
+        enemy_type: EnemyType,
+        boosts: i32,
+        seed: u64,
+        asset_server: &Res<AssetServer>,
+    ) -> Self {
         EnemyBundle {
             enemy_type,
             enemy_impulses: Default::default(),
-            enemy_behavior_tree: enemy_type.get_behavior_tree(),
+            enemy_behavior_tree_def: asset_server.load(enemy_type.get_behavior_tree_path()),
+            enemy_blackboard: Default::default(),
+            enemy_tree_seed: BehaviorTreeSeed(seed),
             health: enemy_type.get_health(boosts),
         }
     }
@@ -145,9 +138,11 @@ fn boost_color(boosts: i32) -> Color {
 pub fn spawn_enemy(
     commands: &mut Commands,
     sprites: &Res<Sprites>,
+    asset_server: &Res<AssetServer>,
     transform: Transform,
     enemy_type: EnemyType,
     boosts: i32,
+    seed: u64,
 ) {
     commands
         .spawn_bundle(SpriteSheetBundle {
@@ -159,6 +154,6 @@ pub fn spawn_enemy(
             },
             ..Default::default()
         })
-        .insert_bundle(EnemyBundle::new(enemy_type, boosts))
+        .insert_bundle(EnemyBundle::new(enemy_type, boosts, seed, asset_server))
         .insert(GameOverCleanup);
 }
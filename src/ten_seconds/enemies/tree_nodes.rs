@@ -1,31 +1,101 @@
+use bevy::reflect::{TypeUuid, Uuid};
+use serde::{Deserialize, Serialize};
+
 use crate::prelude::*;
 
 use super::ai::*;
 
-pub enum EnemyNode {
-    Pathfind(PathfindNode),
-    Attack(AttackNode),
+pub type EnemyNode = UserNode<EnemyWorldView>;
+
+lazy_static! {
+    static ref ENEMY_NODES: UserNodeRegistry<EnemyWorldView, EnemyImpulses> =
+        UserNodeRegistry::new()
+            .register::<PathfindNode>("Pathfind")
+            .register::<AttackNode>("Attack");
 }
 
-impl UserNodeDefinition for EnemyNode {
-    type Model = EnemyWorldView;
+impl UserNodeRegistration for EnemyWorldView {
     type Controller = EnemyImpulses;
+    type Predicate = EnemyPredicate;
+    type Scorer = EnemyScorer;
+
+    fn user_nodes() -> &'static UserNodeRegistry<Self, Self::Controller> {
+        &ENEMY_NODES
+    }
+}
+
+impl TypeUuid for BehaviorTreeDef<EnemyNode> {
+    const TYPE_UUID: Uuid = Uuid::from_u128(0x0b8e5d71_2c6a_4f93_a1d4_7e9c3b5f2a60);
+}
+
+impl TypeUuid for BehaviorTreeLibrary<EnemyNode> {
+    const TYPE_UUID: Uuid = Uuid::from_u128(0x4d2a9e63_7b1c_4e08_9f35_c6a8e1d0b729);
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum EnemyPredicate {
+    HealthBelow(i32),
+    // In tiles walked to the nearest goal.
+    DistanceFromGoalBelow(i32),
+    NeighborTowerOfClass(TowerClass),
+}
+
+impl BehaviorTreePredicate for EnemyPredicate {
+    type Model = EnemyWorldView;
+
+    fn check(&self, model: &Self::Model) -> bool {
+        match self {
+            EnemyPredicate::HealthBelow(health) => model.health < *health,
+            EnemyPredicate::DistanceFromGoalBelow(distance) => model.distance_from_goal < *distance,
+            EnemyPredicate::NeighborTowerOfClass(class) => model
+                .neighbor_towers
+                .iter()
+                .any(|(_, tower_type)| tower_type.class == *class),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum EnemyScorer {
+    Constant(f32),
+    // The cost of the best path to the goal, negated so cheaper paths score higher.
+    PathCost,
+    NeighborTowers,
+    NeighborTowersOfClass(TowerClass),
+    Weighted(f32, Box<EnemyScorer>),
+    Sum(Vec<EnemyScorer>),
+}
 
-    fn create_node(
-        &self,
-    ) -> Box<dyn BehaviorTree<Model = Self::Model, Controller = Self::Controller> + Send + Sync>
-    {
+impl BehaviorTreeScorer for EnemyScorer {
+    type Model = EnemyWorldView;
+
+    fn score(&self, model: &Self::Model) -> f32 {
         match self {
-            Self::Pathfind(node) => node.create_node(),
-            Self::Attack(node) => node.create_node(),
+            EnemyScorer::Constant(score) => *score,
+            EnemyScorer::PathCost => model
+                .next_steps
+                .as_ref()
+                .map(|(_, cost)| -(*cost as f32))
+                .unwrap_or(f32::NEG_INFINITY),
+            EnemyScorer::NeighborTowers => model.neighbor_towers.len() as f32,
+            EnemyScorer::NeighborTowersOfClass(class) => model
+                .neighbor_towers
+                .iter()
+                .filter(|(_, tower_type)| tower_type.class == *class)
+                .count() as f32,
+            EnemyScorer::Weighted(weight, scorer) => weight * scorer.score(model),
+            EnemyScorer::Sum(scorers) => scorers.iter().map(|scorer| scorer.score(model)).sum(),
         }
     }
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct PathfindNode {
     pub name: String,
-    pub idx: usize,
+    // Picked on first use from the enemy's seed, so every enemy sharing a tree definition
+    // takes its own path.
+    #[serde(skip)]
+    pub idx: Option<usize>,
 }
 
 impl BehaviorTree for PathfindNode {
@@ -40,16 +110,32 @@ impl BehaviorTree for PathfindNode {
         // Nothing to do
     }
 
+    fn save_state(self: &Self) -> Option<BehaviorTreeNodeState> {
+        to_node_state(&self.idx)
+    }
+
+    fn load_state(self: &mut Self, state: &BehaviorTreeNodeState) -> Result<(), String> {
+        self.idx = from_node_state(state)?;
+        Ok(())
+    }
+
     fn resume_with(
         self: &mut Self,
         model: &Self::Model,
         controller: &mut Self::Controller,
+        _blackboard: &mut Blackboard,
         gas: &mut Option<i32>,
-        audit: &mut Option<&mut BehaviorTreeAudit>,
+        mut audit: &mut Option<&mut BehaviorTreeAudit>,
     ) -> BehaviorTreeState {
-        if let Some((shortest_paths, distance)) = &model.shortest_paths {
-            let my_path = &shortest_paths[self.idx % shortest_paths.len()];
-            if let Some(next_tile) = my_path.get(1) {
+        audit.enter(&self.name);
+        if !use_gas(gas) {
+            audit.exit(&self.name, BehaviorTreeState::WaitingForGas);
+            return BehaviorTreeState::WaitingForGas;
+        }
+        let result = match &model.next_steps {
+            Some((next_steps, _cost)) if !next_steps.is_empty() => {
+                let idx = *self.idx.get_or_insert(model.seed as usize);
+                let next_tile = &next_steps[idx % next_steps.len()];
                 let target_location = Vec2::new(
                     model.field_offset_size.0.x
                         + model.field_offset_size.1 * (next_tile.0 as f32 + 0.5),
@@ -59,18 +145,19 @@ impl BehaviorTree for PathfindNode {
                 let direction = (target_location - model.location).normalize();
                 controller.move_towards = Some(direction);
                 BehaviorTreeState::Complete
-            } else {
-                BehaviorTreeState::Failed
             }
-        } else {
-            BehaviorTreeState::Failed
-        }
+            // Unreachable, or already at a goal.
+            _ => BehaviorTreeState::Failed,
+        };
+        audit.exit(&self.name, result);
+        result
     }
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct AttackNode {
     pub name: String,
+    #[serde(skip)]
     pub idx: usize,
 }
 
@@ -86,17 +173,61 @@ impl BehaviorTree for AttackNode {
         self.idx = 0;
     }
 
+    fn save_state(self: &Self) -> Option<BehaviorTreeNodeState> {
+        to_node_state(&self.idx)
+    }
+
+    fn load_state(self: &mut Self, state: &BehaviorTreeNodeState) -> Result<(), String> {
+        self.idx = from_node_state(state)?;
+        Ok(())
+    }
+
     fn resume_with(
         self: &mut Self,
         model: &Self::Model,
         controller: &mut Self::Controller,
+        _blackboard: &mut Blackboard,
         gas: &mut Option<i32>,
-        audit: &mut Option<&mut BehaviorTreeAudit>,
+        mut audit: &mut Option<&mut BehaviorTreeAudit>,
     ) -> BehaviorTreeState {
+        audit.enter(&self.name);
+        if !use_gas(gas) {
+            audit.exit(&self.name, BehaviorTreeState::WaitingForGas);
+            return BehaviorTreeState::WaitingForGas;
+        }
         if model.neighbor_towers.len() > 0 {
             controller.attack_tower =
                 Some(model.neighbor_towers[self.idx % model.neighbor_towers.len()].0);
         }
+        audit.exit(&self.name, BehaviorTreeState::Complete);
         BehaviorTreeState::Complete
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn view_at(distance_from_goal: i32) -> EnemyWorldView {
+        EnemyWorldView {
+            now: 0.,
+            field_offset_size: (Vec2::ZERO, 32.),
+            location: Vec2::ZERO,
+            tile: FieldLocation(0, 0),
+            my_type: EnemyType::Basic,
+            health: 3,
+            distance_from_goal,
+            next_steps: None,
+            neighbor_towers: Vec::new(),
+            seed: 0,
+        }
+    }
+
+    #[test]
+    fn distance_from_goal_is_in_tiles() {
+        let near = EnemyPredicate::DistanceFromGoalBelow(3);
+        assert!(near.check(&view_at(2)));
+        assert!(!near.check(&view_at(3)));
+        assert!(!near.check(&view_at(i32::MAX)));
+    }
+}
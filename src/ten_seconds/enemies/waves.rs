@@ -1,14 +1,21 @@
 use bevy::{ecs::entity::Entities, utils::HashSet};
+use rand::Rng;
+use rand_pcg::Pcg32;
 
 use crate::prelude::*;
 
 use super::{ai::EnemyImpulses, spawn_enemy};
+use crate::ten_seconds::seed::GameSeed;
+
+// Salt for the generator behind the waves and the enemies in them.
+const WAVE_RNG: u64 = 1;
 
 #[derive(Debug)]
 pub struct WaveStatus {
     time_left: f32,
-    spawned: Vec<(EnemyType, i32)>,
-    spawns: Vec<(EnemyType, i32)>,
+    // Each enemy's type, boosts and spawner.
+    spawned: Vec<(EnemyType, i32, usize)>,
+    spawns: Vec<(EnemyType, i32, usize)>,
     game_over: bool,
     pub wave_id: i32,
     pub health: i32,
@@ -16,10 +23,11 @@ pub struct WaveStatus {
     pub dust: i32,
     pub tech: i32,
     pub tower_type: TowerClass,
+    rng: Pcg32,
 }
 
-impl Default for WaveStatus {
-    fn default() -> Self {
+impl WaveStatus {
+    pub fn new(seed: &GameSeed) -> Self {
         WaveStatus {
             time_left: 10.,
             spawned: vec![],
@@ -31,11 +39,10 @@ impl Default for WaveStatus {
             dust: 2,
             tech: 0,
             tower_type: TowerClass::Attack,
+            rng: seed.rng_for(WAVE_RNG),
         }
     }
-}
 
-impl WaveStatus {
     pub fn get_countdown_value(&self) -> String {
         format!("{}", self.time_left.floor())
     }
@@ -86,7 +93,8 @@ impl WaveStatus {
         }
     }
 
-    fn drain_timed_spawn(&mut self) -> Option<(EnemyType, i32)> {
+    // Also draws the seed for the enemy's behavior tree.
+    fn drain_timed_spawn(&mut self) -> Option<(EnemyType, i32, usize, u64)> {
         if self.spawns.len() == 0 {
             return None;
         }
@@ -96,18 +104,20 @@ impl WaveStatus {
         if spawn_index >= self.spawned.len() {
             let new_enemy = self.spawns.pop().unwrap();
             self.spawned.push(new_enemy);
-            Some(new_enemy)
+            Some((new_enemy.0, new_enemy.1, new_enemy.2, self.rng.gen()))
         } else {
             None
         }
     }
 
-    fn drain_wave_end(&mut self) -> bool {
+    fn drain_wave_end(&mut self, spawners: usize) -> bool {
         if self.time_left <= 0. {
             self.time_left += 10.;
             self.wave_id += 1;
             self.spawned.clear();
-            self.spawns = get_spawns(self.wave_id);
+            let spawns = get_spawns(self.wave_id, &mut self.rng);
+            let policy = get_spawner_policy(self.wave_id);
+            self.spawns = assign_spawners(policy, spawns, spawners, &mut self.rng);
             true
         } else {
             false
@@ -201,16 +211,18 @@ pub fn wave_system(
     asset_server: Res<AssetServer>,
 ) {
     wave_status.time_left -= time.delta_seconds();
-    if let Some((enemy_type, boosts)) = wave_status.drain_timed_spawn() {
+    if let Some((enemy_type, boosts, spawner, seed)) = wave_status.drain_timed_spawn() {
         spawn_enemy(
             &mut commands,
             &sprites,
-            field.get_spawn_transform(),
+            &asset_server,
+            field.get_spawn_transform(spawner),
             enemy_type,
             boosts,
+            seed,
         );
     }
-    if wave_status.drain_wave_end() {
+    if wave_status.drain_wave_end(field.get_spawner_count()) {
         ev_wave_end.send(WaveEndEvent(wave_status.wave_id - 1));
         audio.play(asset_server.load(wave_status.get_sting()));
     }
@@ -227,11 +239,12 @@ pub fn goal_system(
     audio: Res<Audio>,
 ) {
     let mut despawned = HashSet::new();
-    for (entity, _location) in field.get_enemies_in_tile(&field.get_goal()) {
-        if entities.contains(*entity) {
-            commands.entity(*entity).despawn();
-            despawned.insert(*entity);
-            hit_goal(&mut wave_status, &mut state, &audio, &sounds);
+    for goal in field.get_goals() {
+        for (entity, _location) in field.get_enemies_in_tile(&goal) {
+            if entities.contains(*entity) && despawned.insert(*entity) {
+                commands.entity(*entity).despawn();
+                hit_goal(&mut wave_status, &mut state, &audio, &sounds);
+            }
         }
     }
     for (entity, transform, impulse) in clean_up_query.iter() {
@@ -278,7 +291,60 @@ fn hit_goal(
     }
 }
 
-fn get_spawns(wave_id: i32) -> Vec<(EnemyType, i32)> {
+// How a wave's enemies are shared out between the map's spawners.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum SpawnerPolicy {
+    // Every enemy from the first spawner.
+    First,
+    // Each enemy from the next spawner along.
+    RoundRobin,
+    // Each enemy from a spawner drawn from the wave's generator.
+    Random,
+}
+
+impl SpawnerPolicy {
+    fn get_spawner(self, index: usize, spawners: usize, rng: &mut Pcg32) -> usize {
+        match self {
+            SpawnerPolicy::First => 0,
+            SpawnerPolicy::RoundRobin => index % spawners,
+            SpawnerPolicy::Random => rng.gen_range(0..spawners),
+        }
+    }
+}
+
+fn get_spawner_policy(wave_id: i32) -> SpawnerPolicy {
+    match wave_id {
+        // The tutorial keeps to one spawner, so there's one path to learn on.
+        1..=9 => SpawnerPolicy::First,
+        // The finale comes from every side at once.
+        31 => SpawnerPolicy::RoundRobin,
+        _ => SpawnerPolicy::Random,
+    }
+}
+
+// Which spawner each enemy in the wave comes from. With one spawner nothing is drawn, so
+// seeded games play out as they did before maps could have more.
+fn assign_spawners(
+    policy: SpawnerPolicy,
+    spawns: Vec<(EnemyType, i32)>,
+    spawners: usize,
+    rng: &mut Pcg32,
+) -> Vec<(EnemyType, i32, usize)> {
+    spawns
+        .into_iter()
+        .enumerate()
+        .map(|(index, (enemy_type, boosts))| {
+            let spawner = if spawners <= 1 {
+                0
+            } else {
+                policy.get_spawner(index, spawners, rng)
+            };
+            (enemy_type, boosts, spawner)
+        })
+        .collect()
+}
+
+fn get_spawns(wave_id: i32, rng: &mut Pcg32) -> Vec<(EnemyType, i32)> {
     match wave_id {
         1 => vec![(EnemyType::Basic, 0)],
         2 => vec![(EnemyType::Basic, 0), (EnemyType::Basic, 0)],
@@ -394,7 +460,7 @@ fn get_spawns(wave_id: i32) -> Vec<(EnemyType, i32)> {
                 wave_cost += 10;
             }
             while wave_cost > 0 {
-                match (rand::random::<f32>() * 12 as f32).floor() as i32 {
+                match (rng.gen::<f32>() * 12 as f32).floor() as i32 {
                     9 | 8 => {
                         if wave_cost > 3 {
                             wave.push((EnemyType::Buster, 0));
@@ -447,12 +513,12 @@ fn get_spawns(wave_id: i32) -> Vec<(EnemyType, i32)> {
                     }
                 }
                 if wave_cost > 2 {
-                    let boosted = (rand::random::<f32>() * wave.len() as f32).floor() as usize;
+                    let boosted = (rng.gen::<f32>() * wave.len() as f32).floor() as usize;
                     wave[boosted].1 += 1;
                     wave_cost -= wave[boosted].1;
                 }
                 if wave_cost > 20 {
-                    let boosted = (rand::random::<f32>() * wave.len() as f32).floor() as usize;
+                    let boosted = (rng.gen::<f32>() * wave.len() as f32).floor() as usize;
                     wave[boosted].1 += 1;
                     wave_cost -= wave[boosted].1;
                 }
@@ -461,3 +527,67 @@ fn get_spawns(wave_id: i32) -> Vec<(EnemyType, i32)> {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn spawners_for(policy: SpawnerPolicy, spawners: usize, seed: u64) -> Vec<usize> {
+        let spawns = vec![(EnemyType::Basic, 0); 6];
+        let mut rng = GameSeed(seed).rng_for(WAVE_RNG);
+        assign_spawners(policy, spawns, spawners, &mut rng)
+            .into_iter()
+            .map(|(_, _, spawner)| spawner)
+            .collect()
+    }
+
+    #[test]
+    fn waves_pick_their_policy() {
+        assert_eq!(get_spawner_policy(1), SpawnerPolicy::First);
+        assert_eq!(get_spawner_policy(9), SpawnerPolicy::First);
+        assert_eq!(get_spawner_policy(10), SpawnerPolicy::Random);
+        assert_eq!(get_spawner_policy(31), SpawnerPolicy::RoundRobin);
+        assert_eq!(get_spawner_policy(32), SpawnerPolicy::Random);
+    }
+
+    #[test]
+    fn policies_share_out_spawners() {
+        assert_eq!(spawners_for(SpawnerPolicy::First, 3, 0), [0, 0, 0, 0, 0, 0]);
+        assert_eq!(
+            spawners_for(SpawnerPolicy::RoundRobin, 3, 0),
+            [0, 1, 2, 0, 1, 2]
+        );
+        let random = spawners_for(SpawnerPolicy::Random, 3, 0);
+        assert!(random.iter().all(|spawner| *spawner < 3));
+    }
+
+    #[test]
+    fn one_spawner_draws_nothing() {
+        let mut rng = GameSeed(7).rng_for(WAVE_RNG);
+        let spawns = vec![(EnemyType::Basic, 0); 6];
+        let assigned = assign_spawners(SpawnerPolicy::Random, spawns, 1, &mut rng);
+        assert!(assigned.iter().all(|(_, _, spawner)| *spawner == 0));
+        // The generator is where it started, so the rest of the wave plays out the same.
+        assert_eq!(rng.gen::<u64>(), GameSeed(7).rng_for(WAVE_RNG).gen::<u64>());
+    }
+
+    #[test]
+    fn seeds_repeat_their_spawners() {
+        for seed in 0..8 {
+            assert_eq!(
+                spawners_for(SpawnerPolicy::Random, 4, seed),
+                spawners_for(SpawnerPolicy::Random, 4, seed)
+            );
+        }
+        // Pinned, so a change to what seeded games draw shows up here.
+        assert_eq!(
+            spawners_for(SpawnerPolicy::Random, 4, 51),
+            [2, 0, 1, 0, 2, 3]
+        );
+        // And differ between seeds, or the seed wouldn't matter.
+        assert!((1..8).any(|seed| {
+            spawners_for(SpawnerPolicy::Random, 4, seed)
+                != spawners_for(SpawnerPolicy::Random, 4, 0)
+        }));
+    }
+}
@@ -1,6 +0,0 @@
-pub const FIELD_WIDTH: i32 = 28;
-pub const FIELD_HEIGHT: i32 = 18;
-pub const TILE_SIZE: f32 = 32.0;
-pub const OFFSET: (f32, f32) = (32.0, 32.0);
-pub const SOURCE: (i32, i32) = (0, FIELD_HEIGHT / 2);
-pub const TARGET: (i32, i32) = (FIELD_WIDTH - 1, FIELD_HEIGHT / 2);
@@ -0,0 +1,193 @@
+use std::{cmp::Reverse, collections::BinaryHeap};
+
+use super::{Field, FieldLocation};
+
+// How a flow field prices stepping onto a tile.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PathCost {
+    // What the tile costs, including what dying enemies added to it.
+    Weighted,
+    // Every step alike, for enemies that only care how far they walk.
+    Flat,
+}
+
+impl PathCost {
+    fn step_cost(self, field: &Field, location: &FieldLocation) -> i32 {
+        match self {
+            PathCost::Weighted => field.get_tile_cost(location),
+            PathCost::Flat => 1,
+        }
+    }
+}
+
+// The cost of the cheapest path from every tile to the nearest goal, shared by every enemy
+// pathing the same way.
+pub struct FlowField {
+    cost: PathCost,
+    distances: Vec<Option<i32>>,
+    // The field version the distances were found for.
+    built_for: Option<u64>,
+}
+
+impl FlowField {
+    pub fn new(cost: PathCost) -> Self {
+        FlowField {
+            cost,
+            distances: Vec::new(),
+            built_for: None,
+        }
+    }
+
+    // Only searches again once the field has changed since the last search.
+    pub fn refresh(&mut self, field: &Field) {
+        if self.built_for == Some(field.get_version()) {
+            return;
+        }
+        self.build(field);
+        self.built_for = Some(field.get_version());
+    }
+
+    // Dijkstra outwards from the goals, so one search covers every tile.
+    fn build(&mut self, field: &Field) {
+        let index = |location: &FieldLocation| (location.0 + location.1 * field.width) as usize;
+        let mut distances = vec![None; field.field_locations.len()];
+        let mut frontier = BinaryHeap::new();
+        for goal in field.get_goals() {
+            distances[index(&goal)] = Some(0);
+            frontier.push(Reverse((0, goal.0, goal.1)));
+        }
+        while let Some(Reverse((distance, x, y))) = frontier.pop() {
+            let location = FieldLocation(x, y);
+            // Already reached more cheaply, or nothing can step onto it.
+            if distances[index(&location)] != Some(distance) || !field.is_pathable(&location) {
+                continue;
+            }
+            let through = distance + self.cost.step_cost(field, &location);
+            for (neighbor, _cost) in field.get_neighbors(&location) {
+                let neighbor_distance = &mut distances[index(&neighbor)];
+                if neighbor_distance.is_none_or(|known| through < known) {
+                    *neighbor_distance = Some(through);
+                    frontier.push(Reverse((through, neighbor.0, neighbor.1)));
+                }
+            }
+        }
+        self.distances = distances;
+    }
+
+    pub fn get_distance(&self, field: &Field, location: &FieldLocation) -> Option<i32> {
+        self.distances
+            .get((location.0 + location.1 * field.width) as usize)
+            .copied()
+            .flatten()
+    }
+
+    // Every neighbor a cheapest path from here goes through, with that path's cost. A goal has
+    // nowhere left to go.
+    pub fn get_next_steps(
+        &self,
+        field: &Field,
+        location: &FieldLocation,
+    ) -> Option<(Vec<FieldLocation>, i32)> {
+        let distance = self.get_distance(field, location)?;
+        let next_steps = field
+            .get_pathable_neighbors(location)
+            .into_iter()
+            .map(|(neighbor, _cost)| neighbor)
+            .filter(|neighbor| {
+                self.get_distance(field, neighbor)
+                    .is_some_and(|neighbor_distance| {
+                        neighbor_distance + self.cost.step_cost(field, neighbor) == distance
+                    })
+            })
+            .collect();
+        Some((next_steps, distance))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::prelude::Entity;
+    use crate::ten_seconds::field::{get_pathability, map::MapDef, FieldLocationContents};
+
+    const MAP: &str = r#"(
+        width: 4,
+        height: 3,
+        tile_size: 32.0,
+        offset: (32.0, 32.0),
+        spawners: [(0, 1)],
+        goals: [(3, 1)],
+        terrain: [(Rock, (1, 1), (1, 1))],
+        costs: [((2, 0), 5)],
+    )"#;
+
+    fn test_field() -> Field {
+        let map: MapDef = ron::from_str(MAP).unwrap();
+        let field_locations = map
+            .terrains()
+            .into_iter()
+            .map(|terrain| {
+                let contents = FieldLocationContents::None;
+                (
+                    Entity::from_raw(0),
+                    contents,
+                    get_pathability(terrain, &contents),
+                )
+            })
+            .collect();
+        Field::new(&map, field_locations)
+    }
+
+    #[test]
+    fn weighted_paths_avoid_costly_tiles() {
+        let field = test_field();
+        let mut flow = FlowField::new(PathCost::Weighted);
+        flow.refresh(&field);
+        // Around the rock, the cheap way is below it.
+        assert_eq!(
+            flow.get_next_steps(&field, &FieldLocation(0, 1)),
+            Some((vec![FieldLocation(0, 2)], 5))
+        );
+        assert_eq!(
+            flow.get_next_steps(&field, &FieldLocation(3, 1)),
+            Some((vec![], 0))
+        );
+    }
+
+    #[test]
+    fn flat_paths_tie_around_obstacles() {
+        let field = test_field();
+        let mut flow = FlowField::new(PathCost::Flat);
+        flow.refresh(&field);
+        assert_eq!(
+            flow.get_next_steps(&field, &FieldLocation(0, 1)),
+            Some((vec![FieldLocation(0, 0), FieldLocation(0, 2)], 5))
+        );
+    }
+
+    #[test]
+    fn refresh_follows_cost_changes() {
+        let mut field = test_field();
+        let mut flow = FlowField::new(PathCost::Weighted);
+        flow.refresh(&field);
+        field.increment_tile_cost(&FieldLocation(1, 2), 10);
+        flow.refresh(&field);
+        assert_eq!(
+            flow.get_next_steps(&field, &FieldLocation(0, 1)),
+            Some((vec![FieldLocation(0, 0)], 9))
+        );
+    }
+
+    #[test]
+    fn new_fields_are_searched_again() {
+        let mut field = test_field();
+        let mut flow = FlowField::new(PathCost::Weighted);
+        field.increment_tile_cost(&FieldLocation(1, 2), 10);
+        flow.refresh(&field);
+        flow.refresh(&test_field());
+        assert_eq!(
+            flow.get_next_steps(&test_field(), &FieldLocation(0, 1)),
+            Some((vec![FieldLocation(0, 2)], 5))
+        );
+    }
+}
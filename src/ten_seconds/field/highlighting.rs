@@ -1,4 +1,4 @@
-use crate::{prelude::*, ten_seconds::field::TILE_SIZE};
+use crate::prelude::*;
 
 use super::FieldLocationContents;
 
@@ -0,0 +1,202 @@
+use bevy::reflect::{TypeUuid, Uuid};
+use serde::Deserialize;
+
+use super::terrain::Terrain;
+
+// A field layout, loaded from a .map.ron file under assets/maps. Tiles are (x, y) from the
+// bottom left.
+#[derive(Deserialize, Debug, Clone)]
+pub struct MapDef {
+    pub width: i32,
+    pub height: i32,
+    pub tile_size: f32,
+    // Where the bottom left corner of the field is drawn.
+    pub offset: (f32, f32),
+    // Where enemies come from. Waves say which one each enemy uses.
+    pub spawners: Vec<(i32, i32)>,
+    // Where enemies go. Each heads for whichever it can reach most cheaply.
+    pub goals: Vec<(i32, i32)>,
+    // Rectangles of terrain between two corner tiles, inclusive. Later ones are laid over
+    // earlier ones, and tiles in none are plain ground.
+    #[serde(default)]
+    pub terrain: Vec<(Terrain, (i32, i32), (i32, i32))>,
+    // Path costs for single tiles, in place of what their terrain costs. Costs added in play
+    // never drop below these.
+    #[serde(default)]
+    pub costs: Vec<((i32, i32), i32)>,
+}
+
+impl TypeUuid for MapDef {
+    const TYPE_UUID: Uuid = Uuid::from_u128(0x3a8d5e71_c2b4_4f09_9d6e_7b1f0c4a2e58);
+}
+
+impl MapDef {
+    pub fn index(&self, tile: (i32, i32)) -> usize {
+        (tile.0 + tile.1 * self.width) as usize
+    }
+
+    pub fn contains(&self, tile: (i32, i32)) -> bool {
+        tile.0 >= 0 && tile.0 < self.width && tile.1 >= 0 && tile.1 < self.height
+    }
+
+    // Each tile's terrain, by index.
+    pub fn terrains(&self) -> Vec<Terrain> {
+        let mut terrains = vec![Terrain::Ground; (self.width * self.height) as usize];
+        for (terrain, from, to) in self.terrain.iter() {
+            for y in from.1.min(to.1)..=from.1.max(to.1) {
+                for x in from.0.min(to.0)..=from.0.max(to.0) {
+                    terrains[self.index((x, y))] = *terrain;
+                }
+            }
+        }
+        terrains
+    }
+
+    // The cost of pathing through each tile before anything is played, by index.
+    pub fn base_costs(&self) -> Vec<i32> {
+        let mut base_costs: Vec<i32> = self
+            .terrains()
+            .iter()
+            .map(|terrain| terrain.get_path_cost())
+            .collect();
+        for (tile, cost) in self.costs.iter() {
+            base_costs[self.index(*tile)] = *cost;
+        }
+        base_costs
+    }
+
+    // Everything wrong with the map, so a broken layout is rejected when it loads rather than
+    // when a game starts on it.
+    pub fn validate(&self) -> Vec<String> {
+        let mut errors = Vec::new();
+        if self.width < 2 || self.height < 1 {
+            errors.push(format!(
+                "a {}x{} field is too small",
+                self.width, self.height
+            ));
+            return errors;
+        }
+        let mut check_tile = |what: &str, tile: (i32, i32)| {
+            if !self.contains(tile) {
+                errors.push(format!("{} {:?} is off the field", what, tile));
+            }
+        };
+        for spawner in self.spawners.iter() {
+            check_tile("spawner", *spawner);
+        }
+        for goal in self.goals.iter() {
+            check_tile("goal", *goal);
+        }
+        for (terrain, from, to) in self.terrain.iter() {
+            check_tile(&format!("{:?} corner", terrain), *from);
+            check_tile(&format!("{:?} corner", terrain), *to);
+        }
+        for (tile, _) in self.costs.iter() {
+            check_tile("cost", *tile);
+        }
+        if !errors.is_empty() {
+            // The rest looks tiles up, which needs them on the field.
+            return errors;
+        }
+        if self.tile_size <= 0. {
+            errors.push(format!("tile size {} is not positive", self.tile_size));
+        }
+        if self.spawners.is_empty() {
+            errors.push("there are no spawners".to_string());
+        }
+        if self.goals.is_empty() {
+            errors.push("there are no goals".to_string());
+        }
+        let terrains = self.terrains();
+        let ends: Vec<(i32, i32)> = self
+            .spawners
+            .iter()
+            .chain(self.goals.iter())
+            .copied()
+            .collect();
+        for (index, tile) in ends.iter().enumerate() {
+            if ends[..index].contains(tile) {
+                errors.push(format!("{:?} is more than one spawner or goal", tile));
+            }
+            let terrain = terrains[self.index(*tile)];
+            if !terrain.is_pathable() {
+                errors.push(format!("{:?} is an end on {:?}", tile, terrain));
+            }
+        }
+        for (tile, cost) in self.costs.iter() {
+            if *cost < 1 {
+                errors.push(format!("cost {} at {:?} is below 1", cost, tile));
+            }
+        }
+        errors
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MAP: &str = r#"(
+        width: 4,
+        height: 3,
+        tile_size: 32.0,
+        offset: (32.0, 32.0),
+        spawners: [(0, 1)],
+        goals: [(3, 0), (3, 2)],
+        terrain: [(Mud, (1, 0), (2, 1)), (Rock, (1, 0), (1, 0))],
+        costs: [((2, 2), 5)],
+    )"#;
+
+    #[test]
+    fn shipped_maps_are_valid() {
+        for entry in std::fs::read_dir("assets/maps").unwrap() {
+            let path = entry.unwrap().path();
+            let map: MapDef = ron::from_str(&std::fs::read_to_string(&path).unwrap())
+                .unwrap_or_else(|err| panic!("{} doesn't parse: {}", path.display(), err));
+            assert_eq!(map.validate(), Vec::<String>::new(), "{}", path.display());
+        }
+    }
+
+    #[test]
+    fn terrain_is_laid_in_order() {
+        use Terrain::*;
+        let map: MapDef = ron::from_str(MAP).unwrap();
+        assert!(map.validate().is_empty());
+        assert_eq!(
+            map.terrains(),
+            [Ground, Rock, Mud, Ground, Ground, Mud, Mud, Ground, Ground, Ground, Ground, Ground]
+        );
+        assert_eq!(map.base_costs(), [1, 1, 3, 1, 1, 3, 3, 1, 1, 1, 5, 1]);
+    }
+
+    #[test]
+    fn broken_maps_say_what_is_wrong() {
+        let mut map: MapDef = ron::from_str(MAP).unwrap();
+        map.goals.push((4, 1));
+        map.terrain.push((Terrain::Road, (0, 0), (0, 3)));
+        assert_eq!(
+            map.validate(),
+            [
+                "goal (4, 1) is off the field",
+                "Road corner (0, 3) is off the field",
+            ]
+        );
+        map.goals.pop();
+        map.terrain.pop();
+        map.goals.push((3, 0));
+        map.terrain.push((Terrain::Pad, (0, 1), (0, 1)));
+        map.spawners.push((1, 0));
+        map.costs.push(((0, 0), 0));
+        assert_eq!(
+            map.validate(),
+            [
+                "(0, 1) is an end on Pad",
+                "(1, 0) is an end on Rock",
+                "(3, 0) is more than one spawner or goal",
+                "cost 0 at (0, 0) is below 1",
+            ]
+        );
+        map.spawners.clear();
+        assert_eq!(map.validate()[0], "there are no spawners");
+    }
+}
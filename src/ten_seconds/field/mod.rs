@@ -1,12 +1,18 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+
 use crate::prelude::*;
 
-use self::highlighting::FieldLocationHighlight;
+use self::{highlighting::FieldLocationHighlight, map::MapDef, terrain::Terrain};
 
-use super::{assets::Sprites, towers::TowerType};
+use super::{
+    assets::{MapHandle, Sprites},
+    towers::TowerType,
+};
 
-mod constants;
+pub mod flow_field;
 pub mod highlighting;
-use self::constants::*;
+pub mod map;
+pub mod terrain;
 
 #[derive(PartialEq, Eq, Hash, Clone, Copy, Component, Debug, Inspectable)]
 pub struct FieldLocation(pub i32, pub i32);
@@ -59,40 +65,53 @@ pub struct Field {
     pub height: i32,
     pub tile_size: f32,
     pub offset: Vec2,
-    pub source: (i32, i32),
-    pub target: (i32, i32),
+    pub sources: Vec<(i32, i32)>,
+    pub targets: Vec<(i32, i32)>,
     pub field_locations: Vec<(Entity, FieldLocationContents, Pathability)>,
     pub tile_costs: Vec<i32>,
+    // What each tile cost on the map, which play never takes it below.
+    pub base_costs: Vec<i32>,
+    pub terrain: Vec<Terrain>,
     pub enemies_in_tiles: Vec<Vec<(Entity, Vec2)>>,
+    // Changes whenever a tile's cost or pathability does, for anything cached from them.
+    version: u64,
+}
+
+// Shared by every field, so a new game's field never repeats a version paths were cached for.
+static NEXT_FIELD_VERSION: AtomicU64 = AtomicU64::new(0);
+
+fn next_field_version() -> u64 {
+    NEXT_FIELD_VERSION.fetch_add(1, Ordering::Relaxed)
 }
 
 impl Field {
     pub fn new(
-        width: i32,
-        height: i32,
-        tile_size: f32,
-        offset: Vec2,
-        source: (i32, i32),
-        target: (i32, i32),
+        map: &MapDef,
         field_locations: Vec<(Entity, FieldLocationContents, Pathability)>,
     ) -> Self {
         let mut enemies_in_tiles = Vec::new();
         enemies_in_tiles.resize(field_locations.len(), Vec::new());
-        let mut tile_costs = Vec::new();
-        tile_costs.resize(field_locations.len(), 1);
+        let base_costs = map.base_costs();
         Field {
-            width,
-            height,
-            tile_size,
-            offset,
-            source,
-            target,
+            width: map.width,
+            height: map.height,
+            tile_size: map.tile_size,
+            offset: map.offset.into(),
+            sources: map.spawners.clone(),
+            targets: map.goals.clone(),
             field_locations,
-            tile_costs,
+            tile_costs: base_costs.clone(),
+            base_costs,
+            terrain: map.terrains(),
             enemies_in_tiles,
+            version: next_field_version(),
         }
     }
 
+    pub fn get_version(&self) -> u64 {
+        self.version
+    }
+
     pub fn clear_enemies_in_tiles(&mut self) {
         let mut enemies_in_tiles = Vec::new();
         enemies_in_tiles.resize(self.field_locations.len(), Vec::new());
@@ -142,13 +161,16 @@ impl Field {
 
     pub fn increment_tile_cost(&mut self, location: &FieldLocation, amount: i32) {
         self.tile_costs[(location.0 + location.1 * self.width) as usize] += amount;
+        self.version = next_field_version();
     }
 
     pub fn decrement_tile_cost(&mut self, location: &FieldLocation, amount: i32) {
-        self.tile_costs[(location.0 + location.1 * self.width) as usize] -= amount;
-        if self.tile_costs[(location.0 + location.1 * self.width) as usize] < 1 {
-            self.tile_costs[(location.0 + location.1 * self.width) as usize] = 1;
+        let index = (location.0 + location.1 * self.width) as usize;
+        self.tile_costs[index] -= amount;
+        if self.tile_costs[index] < self.base_costs[index] {
+            self.tile_costs[index] = self.base_costs[index];
         }
+        self.version = next_field_version();
     }
 
     pub fn get_entity(&self, location: &FieldLocation) -> &Entity {
@@ -159,17 +181,16 @@ impl Field {
         &self.get_entity_contents_pathability(location).1
     }
 
+    pub fn get_terrain(&self, location: &FieldLocation) -> Terrain {
+        self.terrain[(location.0 + location.1 * self.width) as usize]
+    }
+
     pub fn update_contents(&mut self, location: &FieldLocation, contents: &FieldLocationContents) {
+        let terrain = self.get_terrain(location);
         let entity_contents_pathability = self.get_entity_contents_pathability_mut(location);
         entity_contents_pathability.1 = contents.clone();
-        match contents {
-            FieldLocationContents::Tower(_, tower_type) => {
-                entity_contents_pathability.2 = Pathability::Unpathable;
-            }
-            _ => {
-                entity_contents_pathability.2 = Pathability::Pathable;
-            }
-        }
+        entity_contents_pathability.2 = get_pathability(terrain, contents);
+        self.version = next_field_version();
     }
 
     pub fn is_pathable(&self, location: &FieldLocation) -> bool {
@@ -203,182 +224,178 @@ impl Field {
         neighbors
     }
 
-    pub fn get_pathable_neighbors_flat_cost(
-        &self,
-        location: &FieldLocation,
-    ) -> Vec<(FieldLocation, i32)> {
-        let mut neighbors = self.get_neighbors(location);
-        neighbors.retain(|(neighbor, _cost)| self.is_pathable(neighbor));
-        for (_neighbor, cost) in neighbors.iter_mut() {
-            *cost = 1;
-        }
-        neighbors
-    }
-
+    // To the nearest goal.
     pub fn estimate_distance_to_goal(&self, location: &FieldLocation) -> i32 {
-        let dx = location.0 - self.target.0;
-        let dy = location.1 - self.target.1;
-        dx * dx + dy * dy
+        self.targets
+            .iter()
+            .map(|target| {
+                let dx = location.0 - target.0;
+                let dy = location.1 - target.1;
+                dx * dx + dy * dy
+            })
+            .min()
+            .unwrap_or(i32::MAX)
     }
 
+    // To the nearest goal.
     pub fn distance_to_goal(&self, location: Vec2) -> f32 {
-        let goal = Vec2::new(
-            self.offset.x + (self.tile_size * (self.target.0 as f32 + 0.5)),
-            self.offset.y + (self.tile_size * (self.target.1 as f32 + 0.5)),
-        );
-        goal.distance(location)
+        self.targets
+            .iter()
+            .map(|target| {
+                let goal = Vec2::new(
+                    self.offset.x + (self.tile_size * (target.0 as f32 + 0.5)),
+                    self.offset.y + (self.tile_size * (target.1 as f32 + 0.5)),
+                );
+                goal.distance(location)
+            })
+            .fold(f32::INFINITY, f32::min)
     }
 
     pub fn is_in_goal(&self, location: &FieldLocation) -> bool {
-        self.estimate_distance_to_goal(location) == 0
+        self.targets.contains(&(location.0, location.1))
     }
 
-    pub fn get_goal(&self) -> FieldLocation {
-        FieldLocation(self.target.0, self.target.1)
+    pub fn get_goals(&self) -> Vec<FieldLocation> {
+        self.targets
+            .iter()
+            .map(|target| FieldLocation(target.0, target.1))
+            .collect()
     }
 
-    pub fn get_spawn_transform(&self) -> Transform {
+    pub fn get_spawners(&self) -> Vec<FieldLocation> {
+        self.sources
+            .iter()
+            .map(|source| FieldLocation(source.0, source.1))
+            .collect()
+    }
+
+    pub fn get_spawner_count(&self) -> usize {
+        self.sources.len()
+    }
+
+    // Spawner indices past the last one wrap around, so waves written for more spawners than
+    // the map has still work.
+    pub fn get_spawn_transform(&self, spawner: usize) -> Transform {
+        let source = self.sources[spawner % self.sources.len()];
         let mut transform = Transform::default();
         transform.translation = Vec3::new(
-            self.offset.x + (self.tile_size * (self.source.0 as f32 + 0.5)),
-            self.offset.y + (self.tile_size * (self.source.1 as f32 + 0.5)),
+            self.offset.x + (self.tile_size * (source.0 as f32 + 0.5)),
+            self.offset.y + (self.tile_size * (source.1 as f32 + 0.5)),
             0.,
         );
         transform
     }
 }
 
-fn spawn_perimeter(commands: &mut Commands, sprites: &Res<Sprites>) {
-    for x in -1..(FIELD_WIDTH + 1) {
-        let mut transform = Transform::default();
-        transform.translation = Vec3::new(
-            (x as f32 * TILE_SIZE) + OFFSET.0 + TILE_SIZE / 2.,
-            OFFSET.1 - TILE_SIZE / 2.,
-            0.0,
-        );
-        commands
-            .spawn_bundle(SpriteSheetBundle {
-                texture_atlas: sprites.field.clone(),
-                sprite: TextureAtlasSprite {
-                    index: 0,
-                    ..Default::default()
-                },
-                transform,
-                ..Default::default()
-            })
-            .insert(GameOverCleanup);
-        let mut transform = Transform::default();
-        transform.translation = Vec3::new(
-            (x as f32 * TILE_SIZE) + OFFSET.0 + TILE_SIZE / 2.,
-            TILE_SIZE * FIELD_HEIGHT as f32 + OFFSET.1 + TILE_SIZE / 2.,
-            0.0,
-        );
-        commands
-            .spawn_bundle(SpriteSheetBundle {
-                texture_atlas: sprites.field.clone(),
-                sprite: TextureAtlasSprite {
-                    index: 0,
-                    ..Default::default()
-                },
-                transform,
-                ..Default::default()
-            })
-            .insert(GameOverCleanup);
-    }
-    for y in 0..FIELD_HEIGHT {
-        let mut transform = Transform::default();
-        transform.translation = Vec3::new(
-            OFFSET.0 - TILE_SIZE / 2.,
-            (y as f32 * TILE_SIZE) + OFFSET.0 + TILE_SIZE / 2.,
-            0.0,
-        );
-        commands
-            .spawn_bundle(SpriteSheetBundle {
-                texture_atlas: sprites.field.clone(),
-                sprite: TextureAtlasSprite {
-                    index: 0,
-                    ..Default::default()
-                },
-                transform,
-                ..Default::default()
-            })
-            .insert(GameOverCleanup);
-        let mut transform = Transform::default();
-        transform.translation = Vec3::new(
-            TILE_SIZE * FIELD_WIDTH as f32 + OFFSET.0 + TILE_SIZE / 2.,
-            (y as f32 * TILE_SIZE) + OFFSET.0 + TILE_SIZE / 2.,
-            0.0,
-        );
-        commands
-            .spawn_bundle(SpriteSheetBundle {
-                texture_atlas: sprites.field.clone(),
-                sprite: TextureAtlasSprite {
-                    index: 0,
-                    ..Default::default()
-                },
-                transform,
-                ..Default::default()
-            })
-            .insert(GameOverCleanup);
-    }
+fn tile_translation(map: &MapDef, x: i32, y: i32, z: f32) -> Vec3 {
+    Vec3::new(
+        (x as f32 * map.tile_size) + map.offset.0 + map.tile_size / 2.,
+        (y as f32 * map.tile_size) + map.offset.1 + map.tile_size / 2.,
+        z,
+    )
 }
-fn spawn_elements(commands: &mut Commands, sprites: &Res<Sprites>) {
-    let mut spawn_transform = Transform::default();
-    spawn_transform.translation = Vec3::new(
-        (SOURCE.0 as f32 * TILE_SIZE) + OFFSET.0 + TILE_SIZE / 2.,
-        (SOURCE.1 as f32 * TILE_SIZE) + OFFSET.1 + TILE_SIZE / 2.,
-        1.0,
-    );
-    commands
-        .spawn_bundle(SpriteSheetBundle {
-            texture_atlas: sprites.field.clone(),
-            sprite: TextureAtlasSprite {
-                index: 1,
-                ..Default::default()
-            },
-            transform: spawn_transform,
-            ..Default::default()
-        })
-        .insert(GameOverCleanup);
 
-    let mut goal_transform = Transform::default();
-    goal_transform.translation = Vec3::new(
-        (TARGET.0 as f32 * TILE_SIZE) + OFFSET.0 + TILE_SIZE / 2.,
-        (TARGET.1 as f32 * TILE_SIZE) + OFFSET.1 + TILE_SIZE / 2.,
-        1.0,
-    );
+fn spawn_tile_sprite(
+    commands: &mut Commands,
+    sprites: &Res<Sprites>,
+    index: usize,
+    translation: Vec3,
+) {
+    let mut transform = Transform::default();
+    transform.translation = translation;
     commands
         .spawn_bundle(SpriteSheetBundle {
             texture_atlas: sprites.field.clone(),
             sprite: TextureAtlasSprite {
-                index: 2,
+                index,
                 ..Default::default()
             },
-            transform: goal_transform,
+            transform,
             ..Default::default()
         })
         .insert(GameOverCleanup);
 }
 
-pub fn spawn_field(mut commands: Commands, sprites: Res<Sprites>) {
+fn spawn_perimeter(commands: &mut Commands, sprites: &Res<Sprites>, map: &MapDef) {
+    for x in -1..(map.width + 1) {
+        spawn_tile_sprite(commands, sprites, 0, tile_translation(map, x, -1, 0.0));
+        spawn_tile_sprite(
+            commands,
+            sprites,
+            0,
+            tile_translation(map, x, map.height, 0.0),
+        );
+    }
+    for y in 0..map.height {
+        spawn_tile_sprite(commands, sprites, 0, tile_translation(map, -1, y, 0.0));
+        spawn_tile_sprite(
+            commands,
+            sprites,
+            0,
+            tile_translation(map, map.width, y, 0.0),
+        );
+    }
+}
+
+fn spawn_elements(commands: &mut Commands, sprites: &Res<Sprites>, map: &MapDef) {
+    for (x, y) in map.spawners.iter() {
+        spawn_tile_sprite(commands, sprites, 1, tile_translation(map, *x, *y, 1.0));
+    }
+    for (x, y) in map.goals.iter() {
+        spawn_tile_sprite(commands, sprites, 2, tile_translation(map, *x, *y, 1.0));
+    }
+}
+
+// Under the field locations, so highlights show over it.
+fn spawn_terrain(commands: &mut Commands, sprites: &Res<Sprites>, map: &MapDef) {
+    for (index, terrain) in map.terrains().iter().enumerate() {
+        if let Some(sprite_index) = terrain.get_sprite_index() {
+            let (x, y) = (index as i32 % map.width, index as i32 / map.width);
+            spawn_tile_sprite(
+                commands,
+                sprites,
+                sprite_index,
+                tile_translation(map, x, y, -0.5),
+            );
+        }
+    }
+}
+
+fn get_pathability(terrain: Terrain, contents: &FieldLocationContents) -> Pathability {
+    match contents {
+        FieldLocationContents::Tower(..) => Pathability::Unpathable,
+        _ if !terrain.is_pathable() => Pathability::Unpathable,
+        _ => Pathability::Pathable,
+    }
+}
+
+pub fn spawn_field(
+    mut commands: Commands,
+    sprites: Res<Sprites>,
+    maps: Res<Assets<MapDef>>,
+    map_handle: Res<MapHandle>,
+) {
+    // Loading waits for the map, and a broken reload keeps the one loaded before.
+    let map = maps
+        .get(&map_handle.0)
+        .expect("the map should have loaded before the game started");
     let mut field_locations = Vec::new();
-    spawn_perimeter(&mut commands, &sprites);
-    spawn_elements(&mut commands, &sprites);
-    for y in 0..FIELD_HEIGHT {
-        for x in 0..FIELD_WIDTH {
+    spawn_perimeter(&mut commands, &sprites, map);
+    spawn_elements(&mut commands, &sprites, map);
+    spawn_terrain(&mut commands, &sprites, map);
+    let terrains = map.terrains();
+    for y in 0..map.height {
+        for x in 0..map.width {
             let mut transform = Transform::default();
-            transform.translation = Vec3::new(
-                (x as f32 * TILE_SIZE) + OFFSET.0 + TILE_SIZE / 2.,
-                (y as f32 * TILE_SIZE) + OFFSET.1 + TILE_SIZE / 2.,
-                0.0,
-            );
-            let contents = if x == SOURCE.0 && y == SOURCE.1 {
+            transform.translation = tile_translation(map, x, y, 0.0);
+            let contents = if map.spawners.contains(&(x, y)) {
                 FieldLocationContents::Spawner
-            } else if x == TARGET.0 && y == TARGET.1 {
+            } else if map.goals.contains(&(x, y)) {
                 FieldLocationContents::Goal
             } else {
                 FieldLocationContents::None
             };
+            let pathability = get_pathability(terrains[map.index((x, y))], &contents);
             let location_entity = commands
                 .spawn_bundle(SpriteSheetBundle {
                     texture_atlas: sprites.field.clone(),
@@ -391,19 +408,10 @@ pub fn spawn_field(mut commands: Commands, sprites: Res<Sprites>) {
                 .insert(contents)
                 .insert(GameOverCleanup)
                 .id();
-            field_locations.push((location_entity, contents, Pathability::Pathable));
+            field_locations.push((location_entity, contents, pathability));
         }
     }
-    let field = Field::new(
-        FIELD_WIDTH,
-        FIELD_HEIGHT,
-        TILE_SIZE,
-        OFFSET.into(),
-        SOURCE,
-        TARGET,
-        field_locations,
-    );
-    commands.insert_resource(field);
+    commands.insert_resource(Field::new(map, field_locations));
 }
 
 pub fn update_contents(
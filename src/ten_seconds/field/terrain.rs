@@ -0,0 +1,59 @@
+use serde::Deserialize;
+
+// What a tile is made of, which decides who can cross it and what can be built there.
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Terrain {
+    #[default]
+    Ground,
+    // Walls off the field. Nothing crosses it or is built on it.
+    Rock,
+    // Slows enemies down, so they path around it if they can.
+    Mud,
+    // Speeds enemies up, but can't be built on.
+    Road,
+    // Takes towers, but enemies can't cross it, so towers on it never block a path.
+    Pad,
+}
+
+impl Terrain {
+    pub fn is_pathable(&self) -> bool {
+        match self {
+            Terrain::Ground | Terrain::Mud | Terrain::Road => true,
+            Terrain::Rock | Terrain::Pad => false,
+        }
+    }
+
+    pub fn is_buildable(&self) -> bool {
+        match self {
+            Terrain::Ground | Terrain::Mud | Terrain::Pad => true,
+            Terrain::Rock | Terrain::Road => false,
+        }
+    }
+
+    // What crossing the tile costs before anything is played on it.
+    pub fn get_path_cost(&self) -> i32 {
+        match self {
+            Terrain::Mud => 3,
+            _ => 1,
+        }
+    }
+
+    pub fn get_speed_multiplier(&self) -> f32 {
+        match self {
+            Terrain::Mud => 0.5,
+            Terrain::Road => 1.5,
+            _ => 1.,
+        }
+    }
+
+    // In the field atlas. Plain ground isn't drawn.
+    pub fn get_sprite_index(&self) -> Option<usize> {
+        match self {
+            Terrain::Ground => None,
+            Terrain::Rock => Some(8),
+            Terrain::Mud => Some(9),
+            Terrain::Road => Some(10),
+            Terrain::Pad => Some(11),
+        }
+    }
+}
@@ -3,24 +3,36 @@ use bevy_inspector_egui::RegisterInspectable;
 use crate::prelude::*;
 
 use self::{
-    assets::{loading_system, Sprites},
+    assets::{
+        build_behavior_trees, loading_system, BehaviorTreeDefLoader, BehaviorTreeLibraryLoader,
+        MapDefLoader, Sprites,
+    },
+    blackboard::BehaviorTreeBlackboard,
     bullets::{update_bullets, Bullet},
+    debugger::{advance_debugger_frame, select_for_debugger, show_debugger, BehaviorTreeDebugger},
     enemies::{
         ai::{
-            move_enemies, steal_ammo, think_for_enemies, BestPaths, BestSeekerPaths, EnemyImpulses,
+            move_enemies, steal_ammo, think_for_enemies, BestPaths, BestSeekerPaths,
+            EnemyBehaviorTree, EnemyImpulses, EnemyThinkBudget,
         },
         damaged::die_enemies,
+        tree_nodes::EnemyNode,
         waves::{goal_system, wave_system, WaveEndEvent, WaveStatus},
     },
     field::{
-        highlighting::highlight_field_location_by_mouse, spawn_field, update_contents,
+        highlighting::highlight_field_location_by_mouse, map::MapDef, spawn_field, update_contents,
         update_enemies_in_tiles, FieldLocationContents,
     },
     health::apply_basic_hits,
+    seed::GameSeed,
     towers::{
-        ai::{assist_towers, shoot_for_towers, think_for_towers, turn_for_towers},
+        ai::{
+            assist_towers, shoot_for_towers, think_for_towers, turn_for_towers, TowerBehaviorTree,
+            TowerThinkBudget,
+        },
         management::{manage_towers, switch_tower_types},
         refresh_towers, spawn_tower,
+        tree_nodes::TowerNode,
     },
     ui::{
         init_game_over, init_main_menu, init_tutorial, init_ui,
@@ -32,16 +44,20 @@ use self::{
 };
 
 pub mod assets;
+pub mod blackboard;
 pub mod bullets;
+pub mod debugger;
 pub mod enemies;
 pub mod field;
 pub mod health;
+pub mod seed;
 pub mod towers;
 pub mod ui;
 pub struct TenSecondTowersPlugin;
 
 impl Plugin for TenSecondTowersPlugin {
     fn build(&self, app: &mut App) {
+        let seed = GameSeed::from_env();
         app.register_inspectable::<FieldLocationContents>()
             .register_inspectable::<FieldLocation>()
             .register_inspectable::<TowerType>()
@@ -49,13 +65,33 @@ impl Plugin for TenSecondTowersPlugin {
             .register_inspectable::<Bullet>()
             .register_inspectable::<EnemyImpulses>()
             .register_inspectable::<Health>()
+            .register_inspectable::<BehaviorTreeBlackboard>()
             .add_event::<BulletHitEvent>()
             .add_event::<DeathEvent>()
             .add_event::<WaveEndEvent>()
-            .insert_resource(WaveStatus::default())
+            .add_asset::<BehaviorTreeDef<TowerNode>>()
+            .add_asset::<BehaviorTreeDef<EnemyNode>>()
+            .add_asset_loader(BehaviorTreeDefLoader::<TowerNode>::new(&["tower.bt.ron"]))
+            .add_asset_loader(BehaviorTreeDefLoader::<EnemyNode>::new(&["enemy.bt.ron"]))
+            .add_asset::<BehaviorTreeLibrary<TowerNode>>()
+            .add_asset::<BehaviorTreeLibrary<EnemyNode>>()
+            .add_asset_loader(BehaviorTreeLibraryLoader::<TowerNode>::new(&[
+                "tower.lib.ron",
+            ]))
+            .add_asset_loader(BehaviorTreeLibraryLoader::<EnemyNode>::new(&[
+                "enemy.lib.ron",
+            ]))
+            .add_asset::<MapDef>()
+            .init_asset_loader::<MapDefLoader>()
+            .insert_resource(WaveStatus::new(&seed))
+            .insert_resource(seed)
             .insert_resource(BestPaths::default())
             .insert_resource(BestSeekerPaths::default())
+            .insert_resource(TowerThinkBudget::default())
+            .insert_resource(EnemyThinkBudget::default())
+            .insert_resource(BehaviorTreeDebugger::from_env())
             .add_startup_system(watch_for_changes)
+            .add_system_to_stage(CoreStage::First, advance_debugger_frame)
             .add_system_set(SystemSet::on_update(AppState::Loading).with_system(loading_system))
             .add_system_set(
                 SystemSet::on_enter(AppState::MainMenu)
@@ -75,6 +111,8 @@ impl Plugin for TenSecondTowersPlugin {
                     .with_system(highlight_field_location_by_mouse)
                     .with_system(update_contents)
                     .with_system(update_enemies_in_tiles)
+                    .with_system(build_behavior_trees::<TowerNode, TowerBehaviorTree>)
+                    .with_system(build_behavior_trees::<EnemyNode, EnemyBehaviorTree>)
                     .with_system(think_for_enemies)
                     .with_system(move_enemies)
                     .with_system(steal_ammo)
@@ -93,11 +131,14 @@ impl Plugin for TenSecondTowersPlugin {
                     .with_system(refresh_towers)
                     .with_system(switch_tower_types)
                     .with_system(tutorial_system)
-                    .with_system(manage_towers),
+                    .with_system(manage_towers)
+                    .with_system(select_for_debugger)
+                    .with_system(show_debugger),
             )
             .add_system_set(SystemSet::on_enter(AppState::GameOver).with_system(init_game_over))
             .add_system_set(
                 SystemSet::on_update(AppState::GameOver)
+                    .with_system(build_behavior_trees::<EnemyNode, EnemyBehaviorTree>)
                     .with_system(think_for_enemies)
                     .with_system(move_enemies)
                     .with_system(fade_in_game_over),
@@ -0,0 +1,37 @@
+use rand::SeedableRng;
+use rand_pcg::Pcg32;
+
+use crate::prelude::*;
+
+// Everything random in a game is drawn from this, so a game can be replayed by starting it
+// with the same SEED.
+pub struct GameSeed(pub u64);
+
+impl GameSeed {
+    pub fn from_env() -> Self {
+        let seed = std::env::var("SEED")
+            .ok()
+            .and_then(|seed| seed.parse().ok())
+            .unwrap_or_else(rand::random);
+        info!("seed: {}", seed);
+        GameSeed(seed)
+    }
+
+    // A generator for one purpose, like the waves, so drawing for one thing doesn't change
+    // what's drawn for another.
+    pub fn rng_for(&self, salt: u64) -> Pcg32 {
+        Pcg32::seed_from_u64(mix_seed(self.0, salt))
+    }
+}
+
+// What a tower's or enemy's behavior tree is built with.
+#[derive(Component, Clone, Copy, Debug, Default)]
+pub struct BehaviorTreeSeed(pub u64);
+
+pub fn mix_seed(seed: u64, salt: u64) -> u64 {
+    // SplitMix64 finalizer, so nearby salts give unrelated seeds.
+    let mut z = seed ^ salt.wrapping_mul(0x9e3779b97f4a7c15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+    z ^ (z >> 31)
+}
@@ -1,6 +1,9 @@
 use crate::{
     prelude::*,
-    ten_seconds::{bullets::spawn_bullet, enemies::ai::EnemyImpulses},
+    ten_seconds::{
+        blackboard::BehaviorTreeBlackboard, bullets::spawn_bullet, debugger::BehaviorTreeDebugger,
+        enemies::ai::EnemyImpulses,
+    },
 };
 
 use super::TowerCooldowns;
@@ -15,6 +18,7 @@ pub struct TowerImpulses {
 
 #[derive(Debug)]
 pub struct TowerWorldView {
+    pub now: f64,
     pub delta_seconds: f32,
     pub location: Vec2,
     pub enemies: Vec<(Vec2, EnemyType, EnemyImpulses)>,
@@ -24,23 +28,57 @@ pub struct TowerWorldView {
     pub neighbor_towers: Vec<(Entity, TowerType)>,
 }
 
+impl BehaviorTreeClock for TowerWorldView {
+    fn now(&self) -> f64 {
+        self.now
+    }
+}
+
 #[derive(Component, Deref, DerefMut)]
 pub struct TowerBehaviorTree(
     pub Box<dyn BehaviorTree<Model = TowerWorldView, Controller = TowerImpulses> + Send + Sync>,
 );
 
+impl From<Box<dyn BehaviorTree<Model = TowerWorldView, Controller = TowerImpulses> + Send + Sync>>
+    for TowerBehaviorTree
+{
+    fn from(
+        tree: Box<
+            dyn BehaviorTree<Model = TowerWorldView, Controller = TowerImpulses> + Send + Sync,
+        >,
+    ) -> Self {
+        TowerBehaviorTree(tree)
+    }
+}
+
+pub const TOWER_GAS_PER_FRAME: i32 = 1024;
+
+#[derive(Deref, DerefMut)]
+pub struct TowerThinkBudget(pub GasBudget);
+
+impl Default for TowerThinkBudget {
+    fn default() -> Self {
+        TowerThinkBudget(GasBudget::new(TOWER_GAS_PER_FRAME))
+    }
+}
+
 pub fn think_for_towers(
     field: Res<Field>,
     time: Res<Time>,
+    mut budget: ResMut<TowerThinkBudget>,
+    mut debugger: ResMut<BehaviorTreeDebugger>,
     mut towers_query: Query<(
+        Entity,
         &Transform,
         &TowerType,
         &mut TowerCooldowns,
         &mut TowerBehaviorTree,
+        &mut BehaviorTreeBlackboard,
         &mut TowerImpulses,
     )>,
     enemies_query: Query<(&Transform, &EnemyType, &EnemyImpulses)>,
 ) {
+    let now = time.seconds_since_startup();
     let delta_seconds = time.delta_seconds();
     let enemies = enemies_query
         .iter()
@@ -49,14 +87,36 @@ pub fn think_for_towers(
             (location, *enemy_type, impulses.clone())
         })
         .collect::<Vec<(Vec2, EnemyType, EnemyImpulses)>>();
-    for (transform, tower_type, mut cooldowns, mut behavior_tree, mut impulses) in
-        towers_query.iter_mut()
-    {
+    let towers = towers_query
+        .iter()
+        .map(|(entity, ..)| entity)
+        .collect::<Vec<Entity>>();
+    let mut gas = budget.get_gas();
+    let mut starved = None;
+    for index in budget.get_order(towers.len()) {
+        let (
+            entity,
+            transform,
+            tower_type,
+            mut cooldowns,
+            mut behavior_tree,
+            mut blackboard,
+            mut impulses,
+        ) = match towers_query.get_mut(towers[index]) {
+            Ok(tower) => tower,
+            Err(_) => continue,
+        };
         let location = get_location_from_transform(transform);
         if let Some(tile) = get_tile_from_location(location, &field) {
             let tile = FieldLocation(tile.0, tile.1);
             cooldowns.pass_time(delta_seconds);
+            if gas == Some(0) {
+                // Keep facing the same way until there's gas. Shots and assists were used up.
+                starved.get_or_insert(index);
+                continue;
+            }
             let model = TowerWorldView {
+                now,
                 delta_seconds,
                 location: get_location_from_transform(transform),
                 enemies: enemies.clone(),
@@ -66,21 +126,39 @@ pub fn think_for_towers(
                 neighbor_towers: get_neighbor_towers(&field, tile),
             };
             let mut new_impulses = TowerImpulses::default();
-            behavior_tree.resume_with(&model, &mut new_impulses, &mut None, &mut None);
-            *impulses = new_impulses;
+            // Names the tower in anything the tree logs.
+            let _span = info_span!("think", ?entity).entered();
+            let mut audit = debugger.get_audit(entity);
+            let state = behavior_tree.resume_with(
+                &model,
+                &mut new_impulses,
+                &mut blackboard,
+                &mut gas,
+                &mut audit.as_mut(),
+            );
+            if let Some(audit) = &audit {
+                debugger.record(entity, audit);
+            }
+            if state == BehaviorTreeState::WaitingForGas {
+                starved.get_or_insert(index);
+            } else {
+                *impulses = new_impulses;
+            }
         }
     }
+    budget.set_starved(starved);
 }
 
 pub fn shoot_for_towers(
     mut commands: Commands,
     sprites: Res<Sprites>,
     sounds: Res<Sounds>,
-    mut towers_query: Query<(&Transform, &TowerImpulses, &mut TowerCooldowns)>,
+    mut towers_query: Query<(&Transform, &mut TowerImpulses, &mut TowerCooldowns)>,
     audio: Res<Audio>,
 ) {
-    for (transform, impulses, mut cooldowns) in towers_query.iter_mut() {
-        if let Some((bullet_type, velocity, lifetime)) = impulses.fire_now {
+    for (transform, mut impulses, mut cooldowns) in towers_query.iter_mut() {
+        // Taken, so a tower waiting for gas doesn't fire the same shot again.
+        if let Some((bullet_type, velocity, lifetime)) = impulses.fire_now.take() {
             if cooldowns.use_ammo() {
                 if velocity.length_squared() < 10. {
                     println!("{:?}", velocity);
@@ -122,11 +200,12 @@ pub fn turn_for_towers(mut towers_query: Query<(&mut Transform, &TowerImpulses)>
 }
 
 pub fn assist_towers(
-    mut towers_query: Query<(Entity, &TowerImpulses)>,
+    mut towers_query: Query<(Entity, &mut TowerImpulses)>,
     mut ammo_query: Query<&mut TowerCooldowns>,
 ) {
-    for (tower_entity, impulse) in towers_query.iter_mut() {
-        if let Some(assisted) = impulse.assist {
+    for (tower_entity, mut impulse) in towers_query.iter_mut() {
+        // Taken, like shots.
+        if let Some(assisted) = impulse.assist.take() {
             let mut can_assist = false;
             if let Ok(assisted_ammo) = ammo_query.get(assisted) {
                 if !assisted_ammo.can_gain_ammo() {
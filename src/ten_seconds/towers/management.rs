@@ -1,6 +1,6 @@
 use crate::{prelude::*, ten_seconds::field::FieldLocationContents};
 
-use super::{ai::TowerBehaviorTree, spawn_tower, upgrade_tower};
+use super::{spawn_tower, tree_nodes::TowerNode, upgrade_tower};
 
 fn set_helper_text(tower_type: TowerClass, mut helper_text_query: Query<(&mut Text, &Name)>) {
     let flavor = match tower_type {
@@ -82,6 +82,7 @@ pub fn switch_tower_types(
 pub fn manage_towers(
     mut commands: Commands,
     sprites: Res<Sprites>,
+    asset_server: Res<AssetServer>,
     mut field: ResMut<Field>,
     mut wave_status: ResMut<WaveStatus>,
     input: Res<Input<MouseButton>>,
@@ -90,7 +91,7 @@ pub fn manage_towers(
     mut field_location_query: Query<&mut FieldLocationContents>,
     upgraded_tower_query: Query<(
         &mut TowerType,
-        &mut TowerBehaviorTree,
+        &mut Handle<BehaviorTreeDef<TowerNode>>,
         &mut TextureAtlasSprite,
     )>,
 ) {
@@ -115,6 +116,7 @@ pub fn manage_towers(
                             spawn_tower(
                                 &mut commands,
                                 &sprites,
+                                &asset_server,
                                 &mut field,
                                 location,
                                 TowerType {
@@ -131,9 +133,12 @@ pub fn manage_towers(
                                 tower_type,
                             )
                         {
-                            if wave_status.upgrade(upgraded_tower_type) {
+                            if upgraded_tower_type.can_upgrade()
+                                && wave_status.upgrade(upgraded_tower_type)
+                            {
                                 upgrade_tower(
                                     upgraded_tower,
+                                    &asset_server,
                                     upgraded_tower_query,
                                     &mut field,
                                     location,
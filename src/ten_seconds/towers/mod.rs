@@ -1,15 +1,22 @@
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+
 use crate::prelude::*;
 
 pub mod ai;
 pub mod management;
-mod tree_nodes;
-use self::ai::{TowerBehaviorTree, TowerImpulses};
-use self::tree_nodes::*;
+pub mod tree_nodes;
+use self::ai::TowerImpulses;
+use self::tree_nodes::TowerNode;
 
+use super::blackboard::BehaviorTreeBlackboard;
 use super::enemies::waves::WaveEndEvent;
 use super::field::FieldLocationContents;
+use super::seed::{mix_seed, BehaviorTreeSeed, GameSeed};
+
+const MAX_TREE_LEVEL: i32 = 4;
 
-#[derive(Debug, Clone, Copy, PartialEq, Inspectable)]
+#[derive(Debug, Clone, Copy, PartialEq, Inspectable, Serialize, Deserialize)]
 pub enum TowerClass {
     Attack,
     Silo,
@@ -30,8 +37,15 @@ impl TowerType {
         self.class.get_cooldowns(self.level)
     }
 
-    fn get_behavior_tree(&self) -> TowerBehaviorTree {
-        self.class.get_behavior_tree(self.level)
+    // Towers without a tree get a handle that never loads, so they sit idle.
+    fn load_behavior_tree(&self, asset_server: &AssetServer) -> Handle<BehaviorTreeDef<TowerNode>> {
+        match self.class.get_behavior_tree_path(self.level) {
+            Some(path) => asset_server.load(&path),
+            None => {
+                warn!("{:?} towers have no behavior tree", self.class);
+                Handle::default()
+            }
+        }
     }
 
     pub fn get_mineral_deconstruct(&self) -> i32 {
@@ -45,80 +59,35 @@ impl TowerType {
     pub fn get_tech_deconstruct(&self) -> i32 {
         self.class.get_tech_deconstruct()
     }
+
+    pub fn can_upgrade(&self) -> bool {
+        self.class
+            .get_max_level()
+            .is_none_or(|max_level| self.level < max_level)
+    }
 }
 
 impl TowerClass {
     pub fn is_blocking(&self) -> bool {
         true
     }
-    fn get_behavior_tree(&self, level: i32) -> TowerBehaviorTree {
-        let tree_def = match self {
-            Self::Attack => {
-                BehaviorTreeDef::Sequence(vec![BehaviorTreeDef::User(FireBulletNode {
-                    name: "Attack".to_string(),
-                    bullet_type: BulletType::Basic {
-                        sprite_index: 0,
-                        damage: 1 + level,
-                    },
-                    fired: false,
-                    speed: 512.,
-                    cooldown: 0.3333,
-                    lifetime: 0.25,
-                })])
-                .create_tree()
-            }
-            Self::Triple => {
-                let mut steps = Vec::new();
-                steps.push(BehaviorTreeDef::User(FireBulletNode {
-                    name: "TripleFirst".to_string(),
-                    bullet_type: BulletType::Basic {
-                        sprite_index: 1,
-                        damage: 1,
-                    },
-                    fired: false,
-                    speed: 512.,
-                    cooldown: 1.,
-                    lifetime: 0.25,
-                }));
-                for i in 0..(level + 2) {
-                    steps.push(BehaviorTreeDef::User(FireBulletNode {
-                        name: format!("Triple{}", i),
-                        bullet_type: BulletType::Basic {
-                            sprite_index: 0,
-                            damage: 1,
-                        },
-                        fired: false,
-                        speed: 512.,
-                        cooldown: 0.05,
-                        lifetime: 0.25,
-                    }));
-                }
-                BehaviorTreeDef::Sequence(steps).create_tree()
-            }
-            Self::BigBomb => {
-                BehaviorTreeDef::Sequence(vec![BehaviorTreeDef::User(FireBulletNode {
-                    name: "Bomb".to_string(),
-                    bullet_type: BulletType::Basic {
-                        sprite_index: 1,
-                        damage: 10,
-                    },
-                    fired: false,
-                    speed: 256.,
-                    cooldown: 1. / (level as f32 + 1.),
-                    lifetime: 0.5,
-                })])
-                .create_tree()
-            }
-            Self::Silo | Self::Wall => {
-                BehaviorTreeDef::Sequence(vec![BehaviorTreeDef::User(RotatingAssistNode {
-                    name: "Reload".to_string(),
-                    idx: 0,
-                })])
-                .create_tree()
-            }
-            _ => todo!("Other towers"),
-        };
-        TowerBehaviorTree(tree_def)
+    // Towers with a tree file per level stop at the last one authored. The rest keep gaining
+    // ammo.
+    fn get_max_level(&self) -> Option<i32> {
+        match self {
+            Self::Attack | Self::Triple | Self::BigBomb => Some(MAX_TREE_LEVEL),
+            _ => None,
+        }
+    }
+    fn get_behavior_tree_path(&self, level: i32) -> Option<String> {
+        match self {
+            Self::Attack => Some(format!("trees/towers/attack_{}.tower.bt.ron", level)),
+            Self::Triple => Some(format!("trees/towers/triple_{}.tower.bt.ron", level)),
+            Self::BigBomb => Some(format!("trees/towers/big_bomb_{}.tower.bt.ron", level)),
+            Self::Silo | Self::Wall => Some("trees/towers/reload.tower.bt.ron".to_string()),
+            // Nothing can fire in four directions yet.
+            Self::Burst => None,
+        }
     }
     fn get_cooldowns(&self, level: i32) -> TowerCooldowns {
         let ammo_left = match self {
@@ -232,17 +201,28 @@ impl TowerCooldowns {
 #[derive(Bundle)]
 struct TowerBundle {
     tower_type: TowerType,
-    tower_behavior_tree: TowerBehaviorTree,
+    tower_behavior_tree_def: Handle<BehaviorTreeDef<TowerNode>>,
+    tower_blackboard: BehaviorTreeBlackboard,
+    tower_tree_seed: BehaviorTreeSeed,
     tower_impulses: TowerImpulses,
     tower_cooldowns: TowerCooldowns,
 }
 
 impl TowerBundle {
-    fn new(tower_type: TowerType) -> Self {
+    fn new(
+        tower_type: TowerType,
+        field_location: FieldLocation,
+        asset_server: &Res<AssetServer>,
+    ) -> Self {
         TowerBundle {
             tower_type,
             tower_impulses: Default::default(),
-            tower_behavior_tree: tower_type.class.get_behavior_tree(tower_type.level),
+            tower_behavior_tree_def: tower_type.load_behavior_tree(asset_server),
+            tower_blackboard: Default::default(),
+            // Seeded by where it stands, so it doesn't matter what was built before it.
+            tower_tree_seed: BehaviorTreeSeed(
+                (field_location.0 as u32 as u64) << 32 | field_location.1 as u32 as u64,
+            ),
             tower_cooldowns: tower_type.class.get_cooldowns(tower_type.level),
         }
     }
@@ -261,20 +241,21 @@ pub fn tower_level_color(boosts: i32) -> Color {
 
 pub fn upgrade_tower(
     entity: Entity,
+    asset_server: &Res<AssetServer>,
     mut upgraded_tower_query: Query<(
         &mut TowerType,
-        &mut TowerBehaviorTree,
+        &mut Handle<BehaviorTreeDef<TowerNode>>,
         &mut TextureAtlasSprite,
     )>,
     field: &mut ResMut<Field>,
     field_location: FieldLocation,
     mut field_location_query: Query<&mut FieldLocationContents>,
 ) {
-    if let Ok((mut tower_type, mut tower_behavior_tree, mut sprite)) =
+    if let Ok((mut tower_type, mut tower_behavior_tree_def, mut sprite)) =
         upgraded_tower_query.get_mut(entity)
     {
         tower_type.level += 1;
-        *tower_behavior_tree = tower_type.get_behavior_tree();
+        *tower_behavior_tree_def = tower_type.load_behavior_tree(asset_server);
         sprite.color = tower_level_color(tower_type.level);
         if let Ok(mut field_location_contents) =
             field_location_query.get_mut(*field.get_entity(&field_location))
@@ -287,6 +268,7 @@ pub fn upgrade_tower(
 pub fn spawn_tower(
     commands: &mut Commands,
     sprites: &Res<Sprites>,
+    asset_server: &Res<AssetServer>,
     field: &mut ResMut<Field>,
     field_location: FieldLocation,
     tower_type: TowerType,
@@ -311,7 +293,7 @@ pub fn spawn_tower(
                 },
                 ..Default::default()
             })
-            .insert_bundle(TowerBundle::new(tower_type))
+            .insert_bundle(TowerBundle::new(tower_type, field_location, asset_server))
             .insert(InGameOnly)
             .id();
         *field_location_contents = FieldLocationContents::Tower(tower_entity, tower_type);
@@ -320,12 +302,13 @@ pub fn spawn_tower(
 
 pub fn refresh_towers(
     mut ev_wave_end: EventReader<WaveEndEvent>,
-    mut cooldowns: Query<(&TowerType, &mut TowerCooldowns)>,
+    seed: Res<GameSeed>,
+    mut cooldowns: Query<(&TowerType, &BehaviorTreeSeed, &mut TowerCooldowns)>,
 ) {
-    for _wave_end in ev_wave_end.iter() {
-        for (tower_type, mut cooldown) in cooldowns.iter_mut() {
+    for wave_end in ev_wave_end.iter() {
+        for (tower_type, tree_seed, mut cooldown) in cooldowns.iter_mut() {
             *cooldown = tower_type.get_cooldowns();
-            cooldown.time_since_shot = rand::random();
+            cooldown.time_since_shot = seed.rng_for(mix_seed(tree_seed.0, wave_end.0 as u64)).gen();
         }
     }
 }
@@ -1,10 +1,113 @@
+use bevy::reflect::{TypeUuid, Uuid};
+use serde::{Deserialize, Serialize};
+
 use crate::{prelude::*, ten_seconds::enemies::ai::EnemyImpulses};
 
 use super::ai::*;
 
-#[derive(Debug, Clone)]
+pub type TowerNode = UserNode<TowerWorldView>;
+
+lazy_static! {
+    static ref TOWER_NODES: UserNodeRegistry<TowerWorldView, TowerImpulses> =
+        UserNodeRegistry::new()
+            .register::<FireBulletNode>("FireBullet")
+            .register::<RotatingAssistNode>("RotatingAssist");
+}
+
+impl UserNodeRegistration for TowerWorldView {
+    type Controller = TowerImpulses;
+    type Predicate = TowerPredicate;
+    type Scorer = TowerScorer;
+
+    fn user_nodes() -> &'static UserNodeRegistry<Self, Self::Controller> {
+        &TOWER_NODES
+    }
+}
+
+impl TypeUuid for BehaviorTreeDef<TowerNode> {
+    const TYPE_UUID: Uuid = Uuid::from_u128(0x6c1f3b2e_94d4_4a57_8e0b_3f2d5a7c9e41);
+}
+
+impl TypeUuid for BehaviorTreeLibrary<TowerNode> {
+    const TYPE_UUID: Uuid = Uuid::from_u128(0x91e7c4b0_5f2d_4a6e_b83c_0d4f7a9e2c15);
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum TowerPredicate {
+    HasAmmo,
+    // Any enemy within this many pixels.
+    EnemyInRange(f32),
+    NeighborTowerOfClass(TowerClass),
+}
+
+impl BehaviorTreePredicate for TowerPredicate {
+    type Model = TowerWorldView;
+
+    fn check(&self, model: &Self::Model) -> bool {
+        match self {
+            TowerPredicate::HasAmmo => model.has_ammo,
+            TowerPredicate::EnemyInRange(range) => model
+                .enemies
+                .iter()
+                .any(|(location, ..)| location.distance_squared(model.location) <= range * range),
+            TowerPredicate::NeighborTowerOfClass(class) => model
+                .neighbor_towers
+                .iter()
+                .any(|(_, tower_type)| tower_type.class == *class),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum TowerScorer {
+    Constant(f32),
+    // 1 with ammo, 0 without.
+    HasAmmo,
+    // How many enemies are within this many pixels.
+    EnemiesInRange(f32),
+    // 1 with an enemy on top of the tower, down to 0 at this many pixels.
+    NearestEnemy(f32),
+    NeighborTowersOfClass(TowerClass),
+    Weighted(f32, Box<TowerScorer>),
+    Sum(Vec<TowerScorer>),
+}
+
+impl BehaviorTreeScorer for TowerScorer {
+    type Model = TowerWorldView;
+
+    fn score(&self, model: &Self::Model) -> f32 {
+        match self {
+            TowerScorer::Constant(score) => *score,
+            TowerScorer::HasAmmo => {
+                if model.has_ammo {
+                    1.
+                } else {
+                    0.
+                }
+            }
+            TowerScorer::EnemiesInRange(range) => model
+                .enemies
+                .iter()
+                .filter(|(location, ..)| location.distance_squared(model.location) <= range * range)
+                .count() as f32,
+            TowerScorer::NearestEnemy(range) => get_closest_enemy(model.location, &model.enemies)
+                .map(|(location, ..)| (1. - location.distance(model.location) / range).max(0.))
+                .unwrap_or(0.),
+            TowerScorer::NeighborTowersOfClass(class) => model
+                .neighbor_towers
+                .iter()
+                .filter(|(_, tower_type)| tower_type.class == *class)
+                .count() as f32,
+            TowerScorer::Weighted(weight, scorer) => weight * scorer.score(model),
+            TowerScorer::Sum(scorers) => scorers.iter().map(|scorer| scorer.score(model)).sum(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct RotatingAssistNode {
     pub name: String,
+    #[serde(skip)]
     pub idx: usize,
 }
 
@@ -20,14 +123,29 @@ impl BehaviorTree for RotatingAssistNode {
         self.idx = 0;
     }
 
+    fn save_state(self: &Self) -> Option<BehaviorTreeNodeState> {
+        to_node_state(&self.idx)
+    }
+
+    fn load_state(self: &mut Self, state: &BehaviorTreeNodeState) -> Result<(), String> {
+        self.idx = from_node_state(state)?;
+        Ok(())
+    }
+
     fn resume_with(
         self: &mut Self,
         model: &Self::Model,
         controller: &mut Self::Controller,
+        _blackboard: &mut Blackboard,
         gas: &mut Option<i32>,
-        audit: &mut Option<&mut BehaviorTreeAudit>,
+        mut audit: &mut Option<&mut BehaviorTreeAudit>,
     ) -> BehaviorTreeState {
-        if model.neighbor_towers.len() > 0 {
+        audit.enter(&self.name);
+        if !use_gas(gas) {
+            audit.exit(&self.name, BehaviorTreeState::WaitingForGas);
+            return BehaviorTreeState::WaitingForGas;
+        }
+        let result = if model.neighbor_towers.len() > 0 {
             if model.has_ammo {
                 controller.assist =
                     Some(model.neighbor_towers[self.idx % model.neighbor_towers.len()].0);
@@ -38,18 +156,26 @@ impl BehaviorTree for RotatingAssistNode {
             }
         } else {
             BehaviorTreeState::Failed
-        }
+        };
+        audit.exit(&self.name, result);
+        result
     }
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct FireBulletNode {
     pub name: String,
     pub bullet_type: BulletType,
+    #[serde(skip)]
     pub fired: bool,
     pub speed: f32,
+    // Since the tower's last shot. Leave it out to time shots with Cooldown or RateLimit instead.
+    #[serde(default)]
     pub cooldown: f32,
     pub lifetime: f32,
+    // Shots sharing a target key aim at the enemy the last of them fired at.
+    #[serde(default)]
+    pub target_key: Option<String>,
 }
 
 impl BehaviorTree for FireBulletNode {
@@ -64,14 +190,28 @@ impl BehaviorTree for FireBulletNode {
         self.fired = false;
     }
 
+    fn save_state(self: &Self) -> Option<BehaviorTreeNodeState> {
+        to_node_state(&self.fired)
+    }
+
+    fn load_state(self: &mut Self, state: &BehaviorTreeNodeState) -> Result<(), String> {
+        self.fired = from_node_state(state)?;
+        Ok(())
+    }
+
     fn resume_with(
         self: &mut Self,
         model: &Self::Model,
         controller: &mut Self::Controller,
+        blackboard: &mut Blackboard,
         gas: &mut Option<i32>,
         mut audit: &mut Option<&mut BehaviorTreeAudit>,
     ) -> BehaviorTreeState {
         audit.enter(&self.name);
+        if !use_gas(gas) {
+            audit.exit(&self.name, BehaviorTreeState::WaitingForGas);
+            return BehaviorTreeState::WaitingForGas;
+        }
         if self.fired {
             audit.mark(&"FireConfirm".to_string());
             audit.exit(&self.name, BehaviorTreeState::Complete);
@@ -82,8 +222,13 @@ impl BehaviorTree for FireBulletNode {
             audit.exit(&self.name, BehaviorTreeState::Waiting);
             BehaviorTreeState::Waiting
         } else {
+            let aim_from = self
+                .target_key
+                .as_ref()
+                .and_then(|key| blackboard.get::<Vec2>(key))
+                .unwrap_or(model.location);
             if let Some((enemy_location, enemy_type, enemy_impulses)) =
-                get_closest_enemy(model.location, &model.enemies)
+                get_closest_enemy(aim_from, &model.enemies)
             {
                 if enemy_location.distance_squared(model.location)
                     > (self.lifetime * self.lifetime * self.speed * self.speed)
@@ -103,6 +248,9 @@ impl BehaviorTree for FireBulletNode {
                     controller.fire_now =
                         Some((self.bullet_type, shoot_dir * self.speed, self.lifetime));
                     self.fired = true;
+                    if let Some(key) = &self.target_key {
+                        blackboard.set(key, *enemy_location);
+                    }
                 }
                 audit.mark(&"Fired".to_string());
                 audit.exit(&self.name, BehaviorTreeState::Waiting);
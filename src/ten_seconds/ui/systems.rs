@@ -1,5 +1,7 @@
 use crate::prelude::*;
 
+use crate::ten_seconds::seed::GameSeed;
+
 use super::HealthCrystal;
 
 const FADE_IN_SECONDS: f32 = 3.;
@@ -8,6 +10,7 @@ const GAME_OVER_OPACITY: f32 = 0.95;
 pub fn fade_in_game_over(
     time: Res<Time>,
     mut state: ResMut<State<AppState>>,
+    mut seed: ResMut<GameSeed>,
     mut wave_status: ResMut<WaveStatus>,
     mut game_over_query: Query<(&mut UiColor, &Name)>,
     input: Res<Input<KeyCode>>,
@@ -25,7 +28,9 @@ pub fn fade_in_game_over(
         }
     }
     if game_is_over && input.get_just_pressed().len() > 0 {
-        *wave_status = WaveStatus::default();
+        // A new game unless SEED pins it to the last one.
+        *seed = GameSeed::from_env();
+        *wave_status = WaveStatus::new(&seed);
         state.set(AppState::InGame);
     }
 }